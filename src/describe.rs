@@ -0,0 +1,639 @@
+use crate::{lookup, Error, FieldKind, Loader, Probe, Result};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::io::Write;
+
+/// Description of an environment variable recognized by the loader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDoc {
+    /// Name of the environment variable, e.g. `APP_HTTP_PORT`.
+    pub name: String,
+    /// Dot-separated path of the field, e.g. `http.port`.
+    pub path: String,
+    /// Kind of the field.
+    pub kind: FieldKind,
+    /// Value of the field after loading environment variables.
+    pub value: Value,
+    /// Whether the field, or a field inside it, is marked as secret by `Loader::secret`.
+    pub secret: bool,
+    /// Example value showing the format for the kind, e.g. `[a, b]` for sequences.
+    /// Empty unless described by `describe_with_examples`.
+    pub example: String,
+}
+
+impl VarDoc {
+    /// The value formatted for display; strings as is, others in flow style.
+    /// Secret values are redacted as `***`.
+    pub fn display_value(&self) -> String {
+        if self.secret {
+            return "***".into();
+        }
+        display(&self.value)
+    }
+}
+
+/// Example value of a variable for a field of `kind`.
+fn example(kind: FieldKind) -> &'static str {
+    match kind {
+        FieldKind::Bool => "true",
+        FieldKind::Int => "8080",
+        FieldKind::Float => "0.5",
+        FieldKind::Char => "a",
+        FieldKind::Str => "text",
+        FieldKind::Bytes => "[104, 105]",
+        FieldKind::Option => "~",
+        FieldKind::Seq | FieldKind::Tuple => "[a, b]",
+        FieldKind::Map => "{a: 1, b: 2}",
+        FieldKind::Struct => "{field: value}",
+        FieldKind::Enum => "Variant",
+    }
+}
+
+/// Format `value` for display; strings as is, others in flow style.
+pub(crate) fn display(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        v => serde_json::to_string(v).unwrap_or_else(|_| format!("{:?}", v)),
+    }
+}
+
+/// Environment variables recognized for a value, from `Loader::iter_vars`.
+pub struct EnvVars {
+    value: Value,
+    probes: Vec<Probe>,
+}
+
+static NULL: Value = Value::Null;
+
+impl EnvVars {
+    /// Iterate over the names of the variables and the current values of their fields,
+    /// in the order of the fields.
+    pub fn iter(&self) -> impl Iterator<Item = (String, &Value)> + '_ {
+        self.probes.iter().map(move |probe| {
+            (
+                probe.name.clone(),
+                lookup(&self.value, &probe.path).unwrap_or(&NULL),
+            )
+        })
+    }
+}
+
+impl Loader {
+    /// Describe the environment variables recognized for `t`, sorted by name.
+    pub fn describe<T: Serialize>(&self, t: &T) -> Result<Vec<VarDoc>> {
+        let ser = self.walk(t)?;
+        let mut docs: Vec<_> = ser
+            .probes
+            .iter()
+            .map(|probe| {
+                let path = probe.path.join(".");
+                VarDoc {
+                    name: probe.name.clone(),
+                    kind: probe.kind,
+                    value: lookup(&ser.value, &probe.path)
+                        .cloned()
+                        .unwrap_or(Value::Null),
+                    secret: self.is_secret(&path),
+                    path,
+                    example: String::new(),
+                }
+            })
+            .collect();
+        docs.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(docs)
+    }
+
+    /// Same as `describe` but with an example value for each variable.
+    pub fn describe_with_examples<T: Serialize>(&self, t: &T) -> Result<Vec<VarDoc>> {
+        let mut docs = self.describe(t)?;
+        for doc in &mut docs {
+            doc.example = example(doc.kind).into();
+        }
+        Ok(docs)
+    }
+
+    /// Same as `describe` but only with the variables of scalar fields, e.g. for docs
+    /// for operators. Sequences, maps and structs, which are set as a whole, are left out,
+    /// while their scalar fields and elements are kept. Options and enums are scalars
+    /// unless they have fields of their own.
+    pub fn describe_leaves<T: Serialize>(&self, t: &T) -> Result<Vec<VarDoc>> {
+        let docs = self.describe(t)?;
+        let nested = |path: &str| {
+            docs.iter().any(|doc| {
+                doc.path
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('.'))
+            })
+        };
+        let leaves = docs
+            .iter()
+            .filter(|doc| match doc.kind {
+                FieldKind::Bool
+                | FieldKind::Int
+                | FieldKind::Float
+                | FieldKind::Char
+                | FieldKind::Str => true,
+                FieldKind::Option | FieldKind::Enum => !nested(&doc.path),
+                _ => false,
+            })
+            .cloned()
+            .collect();
+        Ok(leaves)
+    }
+
+    /// Whether the field at `path` is secret or contains a secret field.
+    pub(crate) fn is_secret(&self, path: &str) -> bool {
+        self.secrets
+            .iter()
+            .any(|s| s == path || s.strip_prefix(path).is_some_and(|s| s.starts_with('.')))
+    }
+
+    /// Name of the environment variable for the field at `path` of `t`, or `None`
+    /// if `t` has no such field.
+    ///
+    /// The path is given in serialized field names, e.g. `&["http", "port"]`, so the
+    /// name reflects `#[serde(rename)]`, `#[serde(rename_all)]` and the name settings
+    /// of the loader.
+    pub fn name_for_value_path<T: Serialize>(
+        &self,
+        t: &T,
+        path: &[&str],
+    ) -> Result<Option<String>> {
+        let ser = self.walk(t)?;
+        Ok(ser
+            .probes
+            .into_iter()
+            .find(|probe| {
+                probe
+                    .path
+                    .iter()
+                    .map(String::as_str)
+                    .eq(path.iter().copied())
+            })
+            .map(|probe| probe.name))
+    }
+
+    /// Names of the environment variables recognized for `T`, sorted, found from `T::default()`.
+    ///
+    /// This needs no instance of `T`, e.g. to generate docs from a build script.
+    /// No variables are read, so the names are the same whatever the environment.
+    pub fn env_var_names<T: Serialize + Default>(&self) -> Result<Vec<String>> {
+        let mut names: Vec<_> = self
+            .walk_probes(&T::default())?
+            .probes
+            .into_iter()
+            .map(|probe| probe.name)
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    /// Same as `describe` but without collecting the descriptions.
+    /// The variables are in the order of the fields instead of sorted by name.
+    pub fn iter_vars<T: Serialize>(&self, t: &T) -> Result<EnvVars> {
+        let ser = self.walk(t)?;
+        Ok(EnvVars {
+            value: ser.value,
+            probes: ser.probes,
+        })
+    }
+
+    /// Write a table of the environment variables recognized for `t` to `w`.
+    ///
+    /// Each row has the name, the kind and the current value of a variable.
+    /// Rows are sorted by name and secret values are redacted.
+    pub fn print_env_vars<T: Serialize, W: Write>(&self, t: &T, mut w: W) -> Result<()> {
+        let rows: Vec<_> = self
+            .describe(t)?
+            .into_iter()
+            .map(|doc| (doc.name.clone(), doc.kind.to_string(), doc.display_value()))
+            .collect();
+        let name_width = rows.iter().map(|r| r.0.len()).fold(4, usize::max);
+        let kind_width = rows.iter().map(|r| r.1.len()).fold(4, usize::max);
+
+        let header = std::iter::once(("NAME".into(), "KIND".into(), "VALUE".into()));
+        for (name, kind, value) in header.chain(rows) {
+            writeln!(
+                w,
+                "{:<nw$}  {:<kw$}  {}",
+                name,
+                kind,
+                value,
+                nw = name_width,
+                kw = kind_width
+            )
+            .map_err(|e| Error::IoError(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe the environment variables recognized for `t`, sorted by name.
+pub fn describe<T: Serialize>(pfx: &str, t: &T) -> Result<Vec<VarDoc>> {
+    Loader::new(pfx).describe(t)
+}
+
+/// Same as `describe` but with an example value for each variable.
+pub fn describe_with_examples<T: Serialize>(pfx: &str, t: &T) -> Result<Vec<VarDoc>> {
+    Loader::new(pfx).describe_with_examples(t)
+}
+
+/// Same as `describe` but only with the variables of scalar fields.
+pub fn describe_leaves<T: Serialize>(pfx: &str, t: &T) -> Result<Vec<VarDoc>> {
+    Loader::new(pfx).describe_leaves(t)
+}
+
+/// Names of the environment variables recognized for `T`, sorted, found from `T::default()`.
+pub fn env_var_names<T: Serialize + Default>(pfx: &str) -> Result<Vec<String>> {
+    Loader::new(pfx).env_var_names::<T>()
+}
+
+/// Write a table of the environment variables recognized for `t` to `w`.
+pub fn print_env_vars<T: Serialize, W: Write>(pfx: &str, t: &T, w: W) -> Result<()> {
+    Loader::new(pfx).print_env_vars(t, w)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::source;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct A {
+        port: u16,
+        db: Db,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Db {
+        password: String,
+        host: Option<String>,
+    }
+
+    fn a() -> A {
+        A {
+            port: 80,
+            db: Db {
+                password: "hunter2".into(),
+                host: None,
+            },
+            tags: vec!["a".into(), "b".into()],
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let docs = Loader::new("describe")
+            .source(HashMap::new())
+            .describe(&a())
+            .unwrap();
+        let names: Vec<_> = docs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "DESCRIBE_DB",
+                "DESCRIBE_DB_HOST",
+                "DESCRIBE_DB_PASSWORD",
+                "DESCRIBE_PORT",
+                "DESCRIBE_TAGS",
+                "DESCRIBE_TAGS_0",
+                "DESCRIBE_TAGS_1",
+            ]
+        );
+        assert_eq!(docs[0].kind, FieldKind::Struct);
+        assert_eq!(docs[3].path, "port");
+        assert_eq!(docs[3].kind, FieldKind::Int);
+        assert_eq!(docs[3].value, Value::Number(80.into()));
+    }
+
+    #[test]
+    fn test_describe_leaves() {
+        #[derive(Serialize)]
+        struct B {
+            a: A,
+            level: Option<u8>,
+            backend: Option<Db>,
+            labels: HashMap<String, String>,
+        }
+
+        let b = B {
+            a: a(),
+            level: None,
+            backend: Some(Db {
+                password: "x".into(),
+                host: Some("localhost".into()),
+            }),
+            labels: HashMap::new(),
+        };
+        let docs = Loader::new("leaves")
+            .source(HashMap::new())
+            .describe_leaves(&b)
+            .unwrap();
+        let names: Vec<_> = docs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "LEAVES_A_DB_HOST",
+                "LEAVES_A_DB_PASSWORD",
+                "LEAVES_A_PORT",
+                "LEAVES_A_TAGS_0",
+                "LEAVES_A_TAGS_1",
+                "LEAVES_BACKEND_HOST",
+                "LEAVES_BACKEND_PASSWORD",
+                "LEAVES_LEVEL",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_with_examples() {
+        #[derive(Serialize)]
+        struct B {
+            b: bool,
+            i: u32,
+            f: f64,
+            c: char,
+            s: String,
+            o: Option<u32>,
+            seq: Vec<u32>,
+            t: (u32, u32),
+            m: HashMap<String, u32>,
+            st: Http,
+            e: E,
+        }
+        #[derive(Serialize)]
+        struct Http {
+            port: u16,
+        }
+        #[derive(Serialize)]
+        enum E {
+            A,
+        }
+
+        let b = B {
+            b: false,
+            i: 0,
+            f: 0.0,
+            c: ' ',
+            s: String::new(),
+            o: None,
+            seq: Vec::new(),
+            t: (0, 0),
+            m: HashMap::new(),
+            st: Http { port: 0 },
+            e: E::A,
+        };
+        let docs = Loader::new("examples")
+            .source(HashMap::new())
+            .describe_with_examples(&b)
+            .unwrap();
+        let examples: Vec<_> = docs
+            .iter()
+            .map(|d| (d.name.as_str(), d.example.as_str()))
+            .collect();
+        assert_eq!(
+            examples,
+            vec![
+                ("EXAMPLES_B", "true"),
+                ("EXAMPLES_C", "a"),
+                ("EXAMPLES_E", "Variant"),
+                ("EXAMPLES_F", "0.5"),
+                ("EXAMPLES_I", "8080"),
+                ("EXAMPLES_M", "{a: 1, b: 2}"),
+                ("EXAMPLES_O", "~"),
+                ("EXAMPLES_S", "text"),
+                ("EXAMPLES_SEQ", "[a, b]"),
+                ("EXAMPLES_ST", "{field: value}"),
+                ("EXAMPLES_ST_PORT", "8080"),
+                ("EXAMPLES_T", "[a, b]"),
+                ("EXAMPLES_T_0", "8080"),
+                ("EXAMPLES_T_1", "8080"),
+            ]
+        );
+
+        // The examples are parsed as values of their kinds.
+        for doc in &docs {
+            let value: Value = serde_yaml::from_str(&doc.example).unwrap();
+            let ok = match doc.kind {
+                FieldKind::Bool => value.is_bool(),
+                FieldKind::Int => value.is_u64(),
+                FieldKind::Float => value.is_f64(),
+                FieldKind::Option => value.is_null(),
+                FieldKind::Seq | FieldKind::Tuple => value.is_sequence(),
+                FieldKind::Map | FieldKind::Struct => value.is_mapping(),
+                _ => value.is_string(),
+            };
+            assert!(ok, "{}: {}", doc.name, doc.example);
+        }
+
+        let docs = Loader::new("examples")
+            .source(HashMap::new())
+            .describe(&b)
+            .unwrap();
+        assert!(docs.iter().all(|d| d.example.is_empty()));
+    }
+
+    #[test]
+    fn test_env_var_names() {
+        #[derive(Serialize, Default)]
+        struct B {
+            port: u16,
+            tags: Vec<String>,
+            http: Http,
+        }
+        #[derive(Serialize, Default)]
+        struct Http {
+            host: Option<String>,
+        }
+
+        let names = env_var_names::<B>("names").unwrap();
+        assert_eq!(
+            names,
+            vec!["NAMES_HTTP", "NAMES_HTTP_HOST", "NAMES_PORT", "NAMES_TAGS"]
+        );
+
+        let docs: Vec<_> = describe("names", &B::default())
+            .unwrap()
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(names, docs);
+    }
+
+    #[test]
+    fn test_env_var_names_ignore_values() {
+        #[derive(Serialize, Default)]
+        struct B {
+            port: u16,
+            tags: Vec<String>,
+        }
+
+        let source = source(&[
+            ("ENVNAMES_PORT", "x"),
+            ("ENVNAMES_TAGS", "[unclosed"),
+            ("ENVNAMES_TAGS_3", "a"),
+        ]);
+        let loader = Loader::new("envnames").source(source);
+        assert!(loader.walk(&B::default()).is_err());
+        assert_eq!(
+            loader.env_var_names::<B>().unwrap(),
+            vec!["ENVNAMES_PORT", "ENVNAMES_TAGS"]
+        );
+    }
+
+    #[test]
+    fn test_iter_vars() {
+        let loader = Loader::new("iter").source(HashMap::new());
+        let vars = loader.iter_vars(&a()).unwrap();
+        let mut items: Vec<_> = vars
+            .iter()
+            .map(|(name, value)| (name, value.clone()))
+            .collect();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let docs: Vec<_> = loader
+            .describe(&a())
+            .unwrap()
+            .into_iter()
+            .map(|d| (d.name, d.value))
+            .collect();
+        assert_eq!(items, docs);
+    }
+
+    #[test]
+    fn test_describe_deterministic() {
+        #[derive(Serialize)]
+        struct B {
+            z: u32,
+            labels: HashMap<String, String>,
+            a: A,
+        }
+
+        let b = B {
+            z: 0,
+            labels: (0..16).map(|i| (i.to_string(), i.to_string())).collect(),
+            a: a(),
+        };
+        let loader = Loader::new("determ").source(HashMap::new());
+        let names = || -> Vec<_> {
+            let vars = loader.iter_vars(&b).unwrap();
+            vars.iter().map(|(name, _)| name).collect()
+        };
+        let (docs, order) = (loader.describe(&b).unwrap(), names());
+        for _ in 0..8 {
+            assert_eq!(loader.describe(&b).unwrap(), docs);
+            assert_eq!(names(), order);
+        }
+    }
+
+    #[test]
+    fn test_describe_unit() {
+        #[derive(Serialize)]
+        struct Unit;
+        #[derive(Serialize)]
+        struct B {
+            a: u32,
+            unit: (),
+            unit_struct: Unit,
+        }
+
+        let b = B {
+            a: 0,
+            unit: (),
+            unit_struct: Unit,
+        };
+        let docs = describe("unit", &b).unwrap();
+        let names: Vec<_> = docs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["UNIT_A"]);
+    }
+
+    #[test]
+    fn test_describe_phantom() {
+        use serde::Deserialize;
+        use std::marker::PhantomData;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        enum Never {}
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+            marker: PhantomData<String>,
+            never: Option<Never>,
+        }
+
+        let b = B {
+            a: 0,
+            marker: PhantomData,
+            never: None,
+        };
+        let docs = describe("phantom", &b).unwrap();
+        let names: Vec<_> = docs.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["PHANTOM_A", "PHANTOM_NEVER"]);
+
+        let source = source(&[("PHANTOM_A", "1"), ("PHANTOM_MARKER", "x")]);
+        let (loaded, warnings) = Loader::new("phantom")
+            .source(source)
+            .load_collect_warnings(&b)
+            .unwrap();
+        assert_eq!(loaded, B { a: 1, ..b });
+        assert_eq!(
+            warnings,
+            vec![crate::Warning::UnusedVar("PHANTOM_MARKER".into())]
+        );
+    }
+
+    #[test]
+    fn test_name_for_value_path() {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Http {
+            listen_port: u16,
+        }
+        #[derive(Serialize)]
+        struct B {
+            #[serde(rename = "web")]
+            http: Http,
+        }
+
+        let b = B {
+            http: Http { listen_port: 80 },
+        };
+        let loader = Loader::new("app");
+        assert_eq!(
+            loader
+                .name_for_value_path(&b, &["web", "listenPort"])
+                .unwrap(),
+            Some("APP_WEB_LISTENPORT".to_string())
+        );
+        assert_eq!(
+            loader
+                .name_for_value_path(&b, &["http", "listen_port"])
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_print_env_vars() {
+        let source = source(&[("PRINT_PORT", "8080")]);
+        let mut out = Vec::new();
+        Loader::new("print")
+            .source(source)
+            .secret("db.password")
+            .print_env_vars(&a(), &mut out)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "NAME               KIND    VALUE\n\
+             PRINT_DB           struct  ***\n\
+             PRINT_DB_HOST      option  null\n\
+             PRINT_DB_PASSWORD  str     ***\n\
+             PRINT_PORT         int     8080\n\
+             PRINT_TAGS         seq     [\"a\",\"b\"]\n\
+             PRINT_TAGS_0       str     a\n\
+             PRINT_TAGS_1       str     b\n"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::{Error, Result, VarSource};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Variables read from a `.env` file.
+///
+/// Each line has the form `KEY=VALUE`, optionally preceded by `export`.
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// * Unquoted values are trimmed, and a `#` preceded by whitespace starts a comment.
+/// * Single-quoted values are taken literally.
+/// * Double-quoted values may span multiple lines, and support the escapes
+///   `\"`, `\\`, `\n`, `\r` and `\t`.
+#[derive(Clone, Debug, Default)]
+pub struct DotEnv {
+    vars: HashMap<String, String>,
+}
+
+impl DotEnv {
+    /// Parse the contents of a `.env` file.
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut vars = HashMap::new();
+
+        let mut lines = s.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (key, value) = match line.find('=') {
+                Some(pos) => (line[..pos].trim(), line[pos + 1..].trim_start()),
+                None => return Err(Error::DotEnvError(i + 1, "missing `=`".into())),
+            };
+            let mut value = value.to_string();
+            if value.starts_with('"') {
+                // Join the following lines until the closing quote.
+                while parse_value(&value).is_err() {
+                    match lines.next() {
+                        Some((_, next)) => {
+                            value.push('\n');
+                            value.push_str(next);
+                        }
+                        None => break,
+                    }
+                }
+            }
+            let value = parse_value(&value).map_err(|e| Error::DotEnvError(i + 1, e))?;
+            vars.insert(key.to_string(), value);
+        }
+
+        Ok(Self { vars })
+    }
+
+    /// Read and parse a `.env` file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let s = std::fs::read_to_string(path)
+            .map_err(|e| Error::IoError(format!("{}: {}", path.display(), e)))?;
+        Self::parse(&s)
+    }
+}
+
+fn parse_value(value: &str) -> std::result::Result<String, String> {
+    let mut chars = value.chars();
+
+    match chars.next() {
+        Some('\'') => match chars.as_str().find('\'') {
+            Some(end) => Ok(chars.as_str()[..end].to_string()),
+            None => Err("unterminated single quote".into()),
+        },
+        Some('"') => {
+            let mut s = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => return Ok(s),
+                    '\\' => match chars.next() {
+                        Some(c @ '"') | Some(c @ '\\') => s.push(c),
+                        Some('n') => s.push('\n'),
+                        Some('r') => s.push('\r'),
+                        Some('t') => s.push('\t'),
+                        Some(c) => {
+                            s.push('\\');
+                            s.push(c);
+                        }
+                        None => break,
+                    },
+                    c => s.push(c),
+                }
+            }
+            Err("unterminated double quote".into())
+        }
+        _ => {
+            let end = value
+                .char_indices()
+                .find(|&(i, c)| c == '#' && value[..i].ends_with(char::is_whitespace))
+                .map(|(i, _)| i)
+                .unwrap_or_else(|| value.len());
+            Ok(value[..end].trim_end().to_string())
+        }
+    }
+}
+
+impl VarSource for DotEnv {
+    fn var(&self, name: &str) -> Result<Option<String>> {
+        self.vars.var(name)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.vars.names()
+    }
+
+    fn label(&self) -> String {
+        ".env".into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Loader;
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_comments() {
+        let env = DotEnv::parse(
+            "# comment\n\
+             \n\
+             HOST=example.com # prod host\n\
+             export PORT=8080\n\
+             URL=http://example.com/#fragment\n\
+             QUOTED=\"a # b\" # comment\n\
+             SINGLE='c # d'\n\
+             EMPTY=\n",
+        )
+        .unwrap();
+        assert_eq!(env.var("HOST").unwrap(), Some("example.com".into()));
+        assert_eq!(env.var("PORT").unwrap(), Some("8080".into()));
+        assert_eq!(
+            env.var("URL").unwrap(),
+            Some("http://example.com/#fragment".into())
+        );
+        assert_eq!(env.var("QUOTED").unwrap(), Some("a # b".into()));
+        assert_eq!(env.var("SINGLE").unwrap(), Some("c # d".into()));
+        assert_eq!(env.var("EMPTY").unwrap(), Some("".into()));
+    }
+
+    #[test]
+    fn test_multiline() {
+        let env = DotEnv::parse(
+            "APP_CERT=\"-----BEGIN CERT-----\n\
+             abc: def\n\
+             -----END CERT-----\"\n\
+             ESCAPED=\"a\\nb\\tc\"\n\
+             NEXT=1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            env.var("APP_CERT").unwrap(),
+            Some("-----BEGIN CERT-----\nabc: def\n-----END CERT-----".into())
+        );
+        assert_eq!(env.var("ESCAPED").unwrap(), Some("a\nb\tc".into()));
+        assert_eq!(env.var("NEXT").unwrap(), Some("1".into()));
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            cert: String,
+        }
+
+        let a = Loader::new("app").source(env).load(&A::default()).unwrap();
+        assert_eq!(a.cert, "-----BEGIN CERT-----\nabc: def\n-----END CERT-----");
+    }
+
+    #[test]
+    fn test_invalid() {
+        match DotEnv::parse("A=1\nB\n") {
+            Err(Error::DotEnvError(2, _)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match DotEnv::parse("A=\"1\n") {
+            Err(Error::DotEnvError(1, _)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use serde_yaml::{Mapping, Value};
+use std::convert::TryFrom;
+
+/// Parse an ISO 8601 duration such as `PT1M30S` into the value of a `Duration`,
+/// `{secs: 90, nanos: 0}`.
+///
+/// Years and months have no fixed length and are rejected. Only the last
+/// component may have a fraction, e.g. `PT1.5S`.
+pub(crate) fn parse_iso8601(s: &str) -> Option<Value> {
+    let mut rest = s.strip_prefix('P')?;
+    let mut time = false;
+    let mut nanos: u128 = 0;
+    let mut empty = true;
+
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('T') {
+            if time {
+                return None;
+            }
+            time = true;
+            empty = true;
+            rest = r;
+            continue;
+        }
+        let end = rest.find(|c: char| !c.is_ascii_digit() && c != '.' && c != ',')?;
+        let (number, unit) = (&rest[..end], rest[end..].chars().next()?);
+        rest = &rest[end + unit.len_utf8()..];
+
+        let unit_nanos: u128 = match (time, unit) {
+            (false, 'W') => 7 * 86_400,
+            (false, 'D') => 86_400,
+            (true, 'H') => 3_600,
+            (true, 'M') => 60,
+            (true, 'S') => 1,
+            _ => return None,
+        } * 1_000_000_000;
+        let (int, frac) = match number.find(['.', ',']) {
+            Some(pos) if rest.is_empty() => (&number[..pos], &number[pos + 1..]),
+            Some(_) => return None,
+            None => (number, ""),
+        };
+        if int.is_empty() || frac.len() > 9 || !frac.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        nanos += int.parse::<u128>().ok()? * unit_nanos;
+        if !frac.is_empty() {
+            let scale = 10u128.pow(frac.len() as u32);
+            nanos += frac.parse::<u128>().ok()? * unit_nanos / scale;
+        }
+        empty = false;
+    }
+    if empty {
+        return None;
+    }
+
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    let mut map = Mapping::new();
+    map.insert("secs".into(), secs.into());
+    map.insert("nanos".into(), ((nanos % 1_000_000_000) as u64).into());
+    Some(Value::Mapping(map))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(s: &str) -> Option<(u64, u64)> {
+        let value = parse_iso8601(s)?;
+        Some((
+            value["secs"].as_u64().unwrap(),
+            value["nanos"].as_u64().unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_parse_iso8601() {
+        assert_eq!(secs("PT2H"), Some((7200, 0)));
+        assert_eq!(secs("PT30S"), Some((30, 0)));
+        assert_eq!(secs("PT1M30S"), Some((90, 0)));
+        assert_eq!(secs("P1DT1S"), Some((86_401, 0)));
+        assert_eq!(secs("P1W"), Some((604_800, 0)));
+        assert_eq!(secs("PT1.5S"), Some((1, 500_000_000)));
+        assert_eq!(secs("PT0,25M"), Some((15, 0)));
+
+        assert_eq!(secs("P"), None);
+        assert_eq!(secs("PT"), None);
+        assert_eq!(secs("P1DT"), None);
+        assert_eq!(secs("P1Y"), None);
+        assert_eq!(secs("PT1H30"), None);
+        assert_eq!(secs("PT1.5M30S"), None);
+        assert_eq!(secs("P1H"), None);
+        assert_eq!(secs("PTXS"), None);
+    }
+}
@@ -0,0 +1,71 @@
+/// Decode the standard base64 `s`, with or without padding.
+pub(crate) fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim_end_matches('=');
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+
+    for c in s.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(v);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    // A single trailing character can't make a byte.
+    if bits >= 6 {
+        return None;
+    }
+    Some(out)
+}
+
+/// Decode the hex `s`, with or without the `0x` prefix.
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            s.get(i..i + 2)
+                .filter(|b| b.bytes().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode_base64("aGVsbG8*"), None);
+        assert_eq!(decode_base64("aGVsb"), None);
+    }
+
+    #[test]
+    fn test_decode_hex() {
+        assert_eq!(decode_hex("deadBEEF").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(decode_hex("0x0001").unwrap(), [0, 1]);
+        assert_eq!(decode_hex("").unwrap(), b"");
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("+1"), None);
+        assert_eq!(decode_hex("éé"), None);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::Result;
+
+/// Syntax of references to other variables in values.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RefSyntax {
+    /// `${VAR}`
+    #[default]
+    Unix,
+    /// `%VAR%`, with `%%` for a literal `%`.
+    Windows,
+    /// Both `${VAR}` and `%VAR%`.
+    Both,
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Expand the references in `s` by `var`. Unset variables expand to empty strings.
+/// Anything which is not a valid reference is kept as is.
+pub(crate) fn expand<F>(s: &str, syntax: RefSyntax, var: F) -> Result<String>
+where
+    F: Fn(&str) -> Result<Option<String>>,
+{
+    let unix = syntax != RefSyntax::Windows;
+    let windows = syntax != RefSyntax::Unix;
+    let mut out = String::new();
+    let mut rest = s;
+
+    while let Some(pos) = rest.find(|c| (unix && c == '$') || (windows && c == '%')) {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        let (name, len) = if rest.starts_with("%%") {
+            out.push('%');
+            rest = &rest[2..];
+            continue;
+        } else if rest.starts_with("${") {
+            match rest.find('}') {
+                Some(end) => (&rest[2..end], end + 1),
+                None => ("", 0),
+            }
+        } else if let Some(tail) = rest.strip_prefix('%') {
+            match tail.find('%') {
+                Some(end) => (&tail[..end], end + 2),
+                None => ("", 0),
+            }
+        } else {
+            ("", 0)
+        };
+
+        if is_name(name) {
+            out.push_str(&var(name)?.unwrap_or_default());
+            rest = &rest[len..];
+        } else {
+            out.push_str(&rest[..1]);
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// Expand a leading `~` to `HOME`, and `$VAR` and `${VAR}` to the variables in path `s` by `var`.
+/// Unset variables expand to empty strings.
+pub(crate) fn expand_path<F>(s: &str, var: F) -> Result<String>
+where
+    F: Fn(&str) -> Result<Option<String>>,
+{
+    let mut out = String::new();
+    let mut rest = s;
+
+    if rest == "~" || rest.starts_with("~/") {
+        out.push_str(&var("HOME")?.unwrap_or_default());
+        rest = &rest[1..];
+    }
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+
+        let (name, len) = match rest.strip_prefix('{') {
+            Some(tail) => match tail.find('}') {
+                Some(end) => (&tail[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            }
+        };
+
+        if is_name(name) {
+            out.push_str(&var(name)?.unwrap_or_default());
+            rest = &rest[len..];
+        } else {
+            out.push('$');
+        }
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn var(name: &str) -> Result<Option<String>> {
+        Ok(match name {
+            "OTHER" => Some("other".into()),
+            "HOME" => Some("/home/eload".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn test_expand_path() {
+        let expand = |s| expand_path(s, var).unwrap();
+        assert_eq!(expand("~/data"), "/home/eload/data");
+        assert_eq!(expand("~"), "/home/eload");
+        assert_eq!(expand("a/~/b"), "a/~/b");
+        assert_eq!(expand("$HOME/data/$OTHER.db"), "/home/eload/data/other.db");
+        assert_eq!(expand("${OTHER}_1/$MISSING/x"), "other_1//x");
+        assert_eq!(expand("$/a$"), "$/a$");
+    }
+
+    #[test]
+    fn test_expand_unix() {
+        let expand = |s| expand(s, RefSyntax::Unix, var).unwrap();
+        assert_eq!(expand("a-${OTHER}-b"), "a-other-b");
+        assert_eq!(expand("${MISSING}"), "");
+        assert_eq!(expand("$OTHER %OTHER% ${OTHER"), "$OTHER %OTHER% ${OTHER");
+    }
+
+    #[test]
+    fn test_expand_windows() {
+        let expand = |s| expand(s, RefSyntax::Windows, var).unwrap();
+        assert_eq!(expand("a-%OTHER%-b"), "a-other-b");
+        assert_eq!(expand("100%% sure"), "100% sure");
+        assert_eq!(expand("50% or 20%"), "50% or 20%");
+        assert_eq!(expand("${OTHER}"), "${OTHER}");
+    }
+
+    #[test]
+    fn test_expand_both() {
+        let expand = |s| expand(s, RefSyntax::Both, var).unwrap();
+        assert_eq!(expand("${OTHER}/%OTHER%"), "other/other");
+    }
+}
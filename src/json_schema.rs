@@ -0,0 +1,141 @@
+use crate::{lookup, FieldKind, Loader, Result};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// JSON Schema type of `kind`, or of `value` for options.
+fn schema_type(kind: FieldKind, value: &Value) -> Option<Value> {
+    let ty = match kind {
+        FieldKind::Bool => "boolean",
+        FieldKind::Int => "integer",
+        FieldKind::Float => "number",
+        FieldKind::Char | FieldKind::Str => "string",
+        FieldKind::Bytes | FieldKind::Seq | FieldKind::Tuple => "array",
+        FieldKind::Map | FieldKind::Struct => "object",
+        FieldKind::Option => {
+            let ty = match value {
+                Value::Null => return None,
+                Value::Bool(_) => "boolean",
+                Value::Number(n) if n.is_f64() => "number",
+                Value::Number(_) => "integer",
+                Value::String(_) => "string",
+                Value::Array(_) => "array",
+                Value::Object(_) => "object",
+            };
+            return Some(json!([ty, "null"]));
+        }
+        FieldKind::Enum => return None,
+    };
+    Some(ty.into())
+}
+
+impl Loader {
+    /// Emit a minimal JSON Schema of `t` for the environment variables recognized for it.
+    ///
+    /// Each field has its type, its current value as `default` and the name of
+    /// its variable as `x-env`. Elements of sequences are not described.
+    pub fn json_schema<T: Serialize>(&self, t: &T) -> Result<Value> {
+        let ser = self.walk(t)?;
+        let mut root = json!({"type": "object", "properties": {}});
+
+        for probe in &ser.probes {
+            let inside_container = (1..probe.path.len()).any(|i| {
+                ser.probes.iter().any(|p| {
+                    p.path[..] == probe.path[..i]
+                        && !matches!(p.kind, FieldKind::Struct | FieldKind::Option)
+                })
+            });
+            if inside_container {
+                continue;
+            }
+
+            let mut node = &mut root;
+            for name in &probe.path {
+                node = node["properties"]
+                    .as_object_mut()
+                    .expect("not an object schema")
+                    .entry(name.clone())
+                    .or_insert_with(|| json!({"properties": {}}));
+            }
+
+            let value = lookup(&ser.value, &probe.path)
+                .and_then(|v| serde_json::to_value(v).ok())
+                .unwrap_or(Value::Null);
+            let schema = node.as_object_mut().expect("not an object schema");
+            if probe.kind != FieldKind::Struct {
+                schema.remove("properties");
+            }
+            if let Some(ty) = schema_type(probe.kind, &value) {
+                schema.insert("type".into(), ty);
+            }
+            schema.insert("default".into(), value);
+            schema.insert("x-env".into(), probe.name.clone().into());
+        }
+
+        Ok(root)
+    }
+}
+
+/// Emit a minimal JSON Schema of `t` for the environment variables recognized for it.
+pub fn json_schema<T: Serialize>(pfx: &str, t: &T) -> Result<Value> {
+    Loader::new(pfx).json_schema(t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct A {
+        port: u16,
+        ratio: f64,
+        debug: bool,
+        db: Db,
+        tags: Vec<String>,
+    }
+
+    #[derive(Serialize)]
+    struct Db {
+        host: Option<String>,
+        user: String,
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let a = A {
+            port: 80,
+            ratio: 0.5,
+            debug: false,
+            db: Db {
+                host: Some("localhost".into()),
+                user: "app".into(),
+            },
+            tags: vec!["a".into()],
+        };
+        let schema = json_schema("schema", &a).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "port": {"type": "integer", "default": 80, "x-env": "SCHEMA_PORT"},
+                    "ratio": {"type": "number", "default": 0.5, "x-env": "SCHEMA_RATIO"},
+                    "debug": {"type": "boolean", "default": false, "x-env": "SCHEMA_DEBUG"},
+                    "db": {
+                        "type": "object",
+                        "default": {"host": "localhost", "user": "app"},
+                        "x-env": "SCHEMA_DB",
+                        "properties": {
+                            "host": {
+                                "type": ["string", "null"],
+                                "default": "localhost",
+                                "x-env": "SCHEMA_DB_HOST",
+                            },
+                            "user": {"type": "string", "default": "app", "x-env": "SCHEMA_DB_USER"},
+                        },
+                    },
+                    "tags": {"type": "array", "default": ["a"], "x-env": "SCHEMA_TAGS"},
+                },
+            })
+        );
+    }
+}
@@ -1,18 +1,80 @@
-use log::*;
 use serde::{de::DeserializeOwned, ser, Serialize};
 use serde_yaml::Value;
-use std::collections::HashSet;
-
-#[derive(thiserror::Error, Debug)]
+use std::collections::{BTreeSet, HashSet};
+
+mod describe;
+mod dotenv;
+#[cfg(any(test, feature = "duration"))]
+mod duration;
+mod encoding;
+mod expand;
+#[cfg(any(test, feature = "json-schema"))]
+mod json_schema;
+mod loader;
+#[cfg(feature = "figment")]
+mod provider;
+mod reload;
+mod report;
+mod source;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tree;
+mod warning;
+
+pub use describe::{
+    describe, describe_leaves, describe_with_examples, env_var_names, print_env_vars, EnvVars,
+    VarDoc,
+};
+pub use dotenv::DotEnv;
+pub use expand::RefSyntax;
+#[cfg(feature = "json-schema")]
+pub use json_schema::json_schema;
+pub use loader::{Coercion, ConflictPolicy, EmptyPolicy, Format, Loader};
+#[cfg(feature = "figment")]
+pub use provider::EnvProvider;
+pub use reload::{env_fingerprint, Reloader};
+pub use report::{Applied, Operation, Report};
+pub use source::{EnvSnapshot, ProcessEnv, VarSource};
+pub use tree::{apply_overrides, to_value_tree};
+pub use warning::Warning;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     #[error("Unpack error {}: {}", _0, _1)]
     UnpackError(String, String),
     #[error("Pack error: {}", _0)]
     PackError(String),
+    #[error("Serialize error: {}", _0)]
+    Serialize(String),
     #[error("Unsupported")]
     Unsupported,
     #[error("Invalid unicode: {}", _0)]
     VarError(String),
+    #[error("Depth exceeded: {}", _0)]
+    DepthExceeded(usize),
+    #[error("IO error: {}", _0)]
+    IoError(String),
+    #[error("Value parse error {}: {}", _0, _1)]
+    ValueParse(String, String),
+    #[error("Deserialize error: {}", _0)]
+    Deserialize(String),
+    #[error("Dotenv error at line {}: {}", _0, _1)]
+    DotEnvError(usize, String),
+    #[error("Value too large: {}", _0)]
+    ValueTooLarge(String),
+    #[error("Conflict: {} overrides a value set by the document", _0)]
+    Conflict(String),
+}
+
+impl Error {
+    fn deserialize(e: serde_yaml::Error) -> Self {
+        Error::Deserialize(e.to_string())
+    }
+
+    /// Error of serializing the base value into a tree, before the fields are walked.
+    fn serialize(e: serde_yaml::Error) -> Self {
+        Error::Serialize(e.to_string())
+    }
 }
 
 impl ser::Error for Error {
@@ -29,305 +91,1478 @@ impl From<serde_yaml::Error> for Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn to_key_str(key: &Value) -> String {
-    match key {
-        Value::String(s) => s.to_uppercase(),
-        e => unreachable!("Key must be string. Found: {:?}", e),
-    }
+/// Kind of a field, known from the serializer method visiting it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldKind {
+    /// `bool`
+    Bool,
+    /// Integers such as `u32` or `i64`.
+    Int,
+    /// `f32` or `f64`
+    Float,
+    /// `char`
+    Char,
+    /// Strings such as `String` or `PathBuf`.
+    Str,
+    /// Byte arrays.
+    Bytes,
+    /// `Option`
+    Option,
+    /// Sequences such as `Vec` or `HashSet`.
+    Seq,
+    /// Tuples, tuple structs or fixed-size arrays.
+    Tuple,
+    /// Maps such as `HashMap`.
+    Map,
+    /// Structs, including internally tagged enum variants.
+    Struct,
+    /// Enum variants other than the internally tagged ones.
+    Enum,
 }
 
-fn find_and_update(value: &mut Value, cur: &str, target: &str, new_value: &Value) -> bool {
-    if cur == target {
-        *value = new_value.clone();
-        return true;
+impl std::fmt::Display for FieldKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            FieldKind::Bool => "bool",
+            FieldKind::Int => "int",
+            FieldKind::Float => "float",
+            FieldKind::Char => "char",
+            FieldKind::Str => "str",
+            FieldKind::Bytes => "bytes",
+            FieldKind::Option => "option",
+            FieldKind::Seq => "seq",
+            FieldKind::Tuple => "tuple",
+            FieldKind::Map => "map",
+            FieldKind::Struct => "struct",
+            FieldKind::Enum => "enum",
+        };
+        f.write_str(s)
     }
+}
+
+/// Parse an integer with a Rust-style radix prefix (`0x`, `0o` or `0b`).
+///
+/// Returns `None` if `s` has no radix prefix.
+fn parse_radix_int(s: &str) -> Option<std::result::Result<Value, ()>> {
+    let (neg, digits) = match s.trim() {
+        s if s.starts_with('-') => (true, &s[1..]),
+        s if s.starts_with('+') => (false, &s[1..]),
+        s => (false, s),
+    };
+    let radix = match digits.get(..2) {
+        Some("0x") | Some("0X") => 16,
+        Some("0o") | Some("0O") => 8,
+        Some("0b") | Some("0B") => 2,
+        _ => return None,
+    };
+    let n = match u64::from_str_radix(&digits[2..], radix) {
+        Ok(n) => n,
+        Err(_) => return Some(Err(())),
+    };
+    Some(if !neg {
+        Ok(Value::Number(n.into()))
+    } else if n <= i64::MAX as u64 + 1 {
+        Ok(Value::Number((n as i64).wrapping_neg().into()))
+    } else {
+        Err(())
+    })
+}
 
+/// Count the nodes of `value`, including the keys of mappings.
+fn count_nodes(value: &Value) -> usize {
     match value {
+        Value::Sequence(seq) => 1 + seq.iter().map(count_nodes).sum::<usize>(),
         Value::Mapping(map) => {
-            for (key, mut value) in map {
-                let key = to_key_str(&key);
-
-                if find_and_update(
-                    &mut value,
-                    &(cur.to_owned() + "_" + &key),
-                    target,
-                    new_value,
-                ) {
-                    return true;
+            1 + map
+                .iter()
+                .map(|(k, v)| count_nodes(k) + count_nodes(v))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+/// Merge `patch` into `base`. Mappings are merged recursively and other values are replaced.
+fn merge(base: &mut Value, patch: Value) {
+    match (base, patch) {
+        (Value::Mapping(base), Value::Mapping(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
                 }
             }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+/// Get the value at `path`, a sequence of mapping keys or sequence indices.
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Mapping(map) => map.get(&Value::String(key.clone())),
+        Value::Sequence(seq) => seq.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Same as `lookup` but returns a mutable reference.
+fn lookup_mut<'a>(value: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter().try_fold(value, |value, key| match value {
+        Value::Mapping(map) => map.get_mut(&Value::String(key.clone())),
+        Value::Sequence(seq) => seq.get_mut(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// Replace the value at `path` with `new_value`.
+///
+/// Returns `false` if `path` doesn't exist in `value`.
+fn find_and_update(value: &mut Value, path: &[String], new_value: &Value) -> bool {
+    match lookup_mut(value, path) {
+        Some(value) => {
+            *value = new_value.clone();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Split the path of an assignment, e.g. `db.hosts[0]`, into mapping keys and sequence indices.
+fn assignment_path(path: &str) -> Option<Vec<String>> {
+    let mut segments = Vec::new();
+    for segment in path.split('.') {
+        let mut parts = segment.split('[');
+        let key = parts.next().unwrap_or_default();
+        if key.is_empty() {
+            return None;
+        }
+        segments.push(key.to_string());
+        for index in parts {
+            let index = index.strip_suffix(']')?;
+            index.parse::<usize>().ok()?;
+            segments.push(index.to_string());
+        }
+    }
+    Some(segments)
+}
+
+/// Remove the entry at `path` from the mapping containing it.
+fn remove_entry(value: &mut Value, path: &[String]) {
+    if let Some((key, parent)) = path.split_last() {
+        if let Some(Value::Mapping(map)) = lookup_mut(value, parent) {
+            map.remove(&Value::String(key.clone()));
+        }
+    }
+}
 
-            false
+/// Collect the paths of the leaves of `value` into `leaves`. Empty mappings are leaves.
+fn leaf_paths(value: &Value, path: &mut Vec<String>, leaves: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, value) in map {
+                if let Some(key) = key.as_str() {
+                    path.push(key.into());
+                    leaf_paths(value, path, leaves);
+                    path.pop();
+                }
+            }
         }
-        _ => false,
+        _ => leaves.push(path.clone()),
     }
 }
 
-pub struct Serializer {
+/// Environment variable visited by the serializer.
+#[derive(Clone)]
+struct Probe {
+    name: String,
+    path: Vec<String>,
+    kind: FieldKind,
+}
+
+pub struct Serializer<'a> {
+    loader: &'a Loader,
     curpath: Vec<String>,
     paths: HashSet<String>,
+    probes: Vec<Probe>,
+    applied: Vec<Applied>,
+    indices: Vec<usize>,
+    variants: Vec<&'static str>,
+    warnings: Vec<Warning>,
+    /// Errors of the fields collected instead of being returned, if lenient.
+    errors: Option<Vec<Error>>,
+    /// Whether the current field is the inner value of an option, already loaded.
+    inner: bool,
+    /// Key of the map entry being walked, if it is a string.
+    key: Option<String>,
+    /// Whether the prefix is an included one, read without aliases and overlays.
+    included: bool,
+    /// Paths of the `u8` fields, telling which sequences are bytes.
+    bytes: HashSet<Vec<String>>,
+    /// Sorted names of the source, for case-insensitive lookups.
+    names: Option<Vec<String>>,
+    /// Whether the current field is a `Duration`.
+    #[cfg(feature = "duration")]
+    duration: bool,
+    /// Spans of the fields being walked, entered by `enter` and exited by `exit`.
+    #[cfg(feature = "tracing")]
+    spans: Vec<tracing::span::EnteredSpan>,
     value: Value,
+    /// Whether `value` is changed from the base value by any variable.
+    modified: bool,
+    /// Names and values read by `var`, resolved through candidates, aliases and overlays.
+    reads: Vec<(String, String)>,
+    /// Whether to only collect the probes, reading no variables as if the source was empty.
+    probe_only: bool,
 }
 
-impl Serializer {
-    fn new(prefix: &str, value: Value) -> Self {
+impl<'a> Serializer<'a> {
+    fn new(loader: &'a Loader, prefix: &str, value: Value) -> Self {
         Self {
-            curpath: vec![prefix.to_uppercase()],
+            loader,
+            curpath: vec![prefix.into()],
             paths: HashSet::new(),
+            probes: Vec::new(),
+            applied: Vec::new(),
+            indices: Vec::new(),
+            variants: Vec::new(),
+            warnings: Vec::new(),
+            errors: None,
+            inner: false,
+            key: None,
+            included: false,
+            bytes: HashSet::new(),
+            names: None,
+            #[cfg(feature = "duration")]
+            duration: false,
+            #[cfg(feature = "tracing")]
+            spans: Vec::new(),
             value,
+            modified: false,
+            reads: Vec::new(),
+            probe_only: false,
         }
     }
 
-    fn enter(&mut self, name: &str) {
-        self.curpath.push(name.to_uppercase());
+    /// Collect the error of `r` instead of returning it if lenient.
+    fn recover(&mut self, r: Result<()>) -> Result<()> {
+        match (r, &mut self.errors) {
+            (Err(e), Some(errors)) => {
+                errors.push(e);
+                Ok(())
+            }
+            (r, _) => r,
+        }
+    }
+
+    fn enter(&mut self, name: &str) -> Result<()> {
+        match self.loader.max_depth {
+            Some(max) if self.curpath.len() > max => Err(Error::DepthExceeded(max)),
+            _ => {
+                self.curpath.push(name.into());
+                #[cfg(feature = "tracing")]
+                self.spans
+                    .push(tracing::trace_span!("field", path = %self.field_path()).entered());
+                Ok(())
+            }
+        }
     }
 
     fn exit(&mut self) {
+        #[cfg(feature = "tracing")]
+        self.spans.pop();
         self.curpath.pop();
     }
 
     fn path(&self) -> String {
-        self.curpath.join("_")
+        match self.candidates().first() {
+            Some(name) => name.clone(),
+            None if self.included => self.loader.var_name(&self.curpath),
+            None => self.loader.path_name(&self.curpath),
+        }
     }
 
-    fn load(&mut self) -> Result<()> {
-        let path = self.path();
-
-        if !self.paths.insert(path.clone()) {
-            warn!("warning: environment variable {} is ambiguous", path);
+    /// Candidate names of the variable of the current field set by `Loader::candidates`.
+    fn candidates(&self) -> &[String] {
+        if self.included {
+            return &[];
         }
+        self.loader
+            .candidates
+            .get(&self.field_path())
+            .map_or(&[], |names| &names[..])
+    }
 
-        match std::env::var(&path) {
-            Ok(val) => {
-                let val = if val.is_empty() { "~".into() } else { val };
-                let val = serde_yaml::from_str(&val)?;
-                let target = self.path().clone();
-                let prefix = self.curpath[0].clone();
-                find_and_update(&mut self.value, &prefix, &target, &val);
-                Ok(())
-            }
-            Err(std::env::VarError::NotPresent) => Ok(()),
-            Err(e) => Err(Error::VarError(e.to_string())),
+    /// Walk the child `value` named `name` unless it is skipped.
+    fn child<T: ?Sized + Serialize>(&mut self, name: &str, value: &T) -> Result<()> {
+        self.enter(name)?;
+        if self.loader.skips.contains(&self.field_path()) {
+            let path = self.path();
+            self.paths.insert(path);
+        } else if self.in_scope() || self.contains_scope() {
+            value.serialize(&mut *self)?;
         }
+        self.exit();
+        Ok(())
     }
-}
-
-impl<'a> ser::Serializer for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
-    type SerializeSeq = Self;
-    type SerializeTuple = Self;
-    type SerializeTupleStruct = Self;
-    type SerializeTupleVariant = Self;
-    type SerializeMap = Self;
-    type SerializeStruct = Self;
-    type SerializeStructVariant = Self;
 
-    fn serialize_bool(self, _: bool) -> Result<()> {
-        self.load()
+    /// Whether the current field is in one of the scopes of the loader.
+    fn in_scope(&self) -> bool {
+        let path = self.field_path();
+        self.loader.scopes.is_empty()
+            || self.loader.scopes.iter().any(|scope| {
+                path == *scope
+                    || path
+                        .strip_prefix(scope.as_str())
+                        .is_some_and(|rest| rest.starts_with('.'))
+            })
     }
 
-    fn serialize_i8(self, _: i8) -> Result<()> {
-        self.load()
+    /// Whether one of the scopes of the loader is inside the current field.
+    fn contains_scope(&self) -> bool {
+        let path = self.field_path();
+        self.loader.scopes.iter().any(|scope| {
+            scope
+                .strip_prefix(path.as_str())
+                .is_some_and(|rest| rest.starts_with('.'))
+        })
     }
 
-    fn serialize_i16(self, _: i16) -> Result<()> {
-        self.load()
+    /// Dot-separated path of the current field without the prefix, e.g. `http.port`.
+    fn field_path(&self) -> String {
+        self.curpath[1..].join(".")
     }
 
-    fn serialize_i32(self, _: i32) -> Result<()> {
-        self.load()
+    /// Add the variable of the field being walked to the errors raised by its `Serialize`,
+    /// to tell them from the errors of serializing the base value.
+    fn pack_context(&self, e: Error) -> Error {
+        match e {
+            Error::PackError(msg) => Error::PackError(format!("{}: {}", self.path(), msg)),
+            e => e,
+        }
     }
 
-    fn serialize_i64(self, _: i64) -> Result<()> {
-        self.load()
+    fn load(&mut self, kind: FieldKind) -> Result<()> {
+        if std::mem::take(&mut self.inner) || !self.in_scope() {
+            return Ok(());
+        }
+        let path = self.path();
+        if !self.loader.is_allowed(&path) {
+            return Ok(());
+        }
+        self.load_as(kind, path)
     }
 
-    fn serialize_u8(self, _: u8) -> Result<()> {
-        self.load()
+    /// Load the current field from the variable `path`.
+    fn load_as(&mut self, kind: FieldKind, path: String) -> Result<()> {
+        let r = self.try_load(kind, path);
+        self.recover(r)
     }
 
-    fn serialize_u16(self, _: u16) -> Result<()> {
-        self.load()
+    fn try_load(&mut self, kind: FieldKind, path: String) -> Result<()> {
+        if !self.paths.insert(path.clone()) {
+            self.warnings.push(Warning::Ambiguous(path.clone()));
+        }
+        self.probes.push(Probe {
+            name: path.clone(),
+            path: self.curpath[1..].to_vec(),
+            kind,
+        });
+
+        let mut name = path.clone();
+        let mut val = self.read(&path, Some(kind))?;
+        if kind == FieldKind::Bool {
+            if let Some((negated, negated_val)) = self.read_negated(&path, val.is_some())? {
+                name = negated;
+                val = Some(negated_val);
+            }
+        }
+        if let Some(val) = val {
+            if let Some(f) = self.loader.validators.get(&self.field_path()) {
+                f(&val).map_err(|e| Error::UnpackError(name.clone(), e))?;
+            }
+            // Structs are merged so that the fields not in the variable are kept.
+            let updated = match lookup_mut(&mut self.value, &self.curpath[1..]) {
+                Some(node) if kind == FieldKind::Struct => {
+                    merge(node, val.clone());
+                    true
+                }
+                _ => find_and_update(&mut self.value, &self.curpath[1..], &val),
+            };
+            #[cfg(feature = "tracing")]
+            tracing::trace!(name = %name, result = if updated { "applied" } else { "present" });
+            if updated {
+                self.modified = true;
+                let operation = if name != path {
+                    Operation::Negate
+                } else if self.loader.coercions.contains_key(&self.field_path()) {
+                    Operation::SetRaw
+                } else {
+                    Operation::Set
+                };
+                self.applied.push(Applied {
+                    name,
+                    path: self.curpath[1..].to_vec(),
+                    value: val,
+                    secret: self.loader.is_secret(&self.field_path()),
+                    operation,
+                });
+            } else if let Some(variant) = self.variants.last() {
+                return Err(Error::UnpackError(
+                    path,
+                    format!("variant {} is not selected", variant),
+                ));
+            }
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(name = %name, result = "absent");
+        }
+        Ok(())
     }
 
-    fn serialize_u32(self, _: u32) -> Result<()> {
-        self.load()
+    /// Get the variable `path` of the current field from the source,
+    /// falling back to the prefix aliases.
+    fn var(&mut self, path: &str) -> Result<Option<String>> {
+        let val = self.resolve(path)?;
+        if let Some(val) = &val {
+            self.reads.push((path.into(), val.clone()));
+        }
+        Ok(val)
     }
 
-    fn serialize_u64(self, _: u64) -> Result<()> {
-        self.load()
+    fn resolve(&mut self, path: &str) -> Result<Option<String>> {
+        if self.probe_only {
+            return Ok(None);
+        }
+        if let Some(val) = self.lookup(path)? {
+            let mut origins = self.loader.source.origins(path)?.into_iter();
+            if let (Some(source), shadowed) = (origins.next(), origins.collect::<Vec<_>>()) {
+                if !shadowed.is_empty() {
+                    self.warnings
+                        .push(Warning::Shadowed(path.into(), source, shadowed));
+                }
+            }
+            return Ok(Some(val));
+        }
+        if let Some((first, rest)) = self.candidates().split_first() {
+            if first == path {
+                let rest = rest.to_vec();
+                self.paths.extend(rest.iter().cloned());
+                for name in rest {
+                    if let Some(val) = self.lookup(&name)? {
+                        return Ok(Some(val));
+                    }
+                }
+            }
+        }
+        if self.included {
+            return Ok(None);
+        }
+        for alias in &self.loader.prefix_aliases {
+            let mut segments = self.curpath.clone();
+            segments[0] = alias.clone();
+            let name = self.loader.var_name(&segments);
+            if let Some(val) = self.lookup(&name)? {
+                self.warnings
+                    .push(Warning::DeprecatedPrefix(name, path.into()));
+                return Ok(Some(val));
+            }
+        }
+        match self.loader.active_overlay()? {
+            Some(overlay) => overlay.var(path),
+            None => Ok(None),
+        }
     }
 
-    fn serialize_f32(self, _: f32) -> Result<()> {
-        self.load()
+    /// Remove the entry `key` of the current map if its variable is set to
+    /// the sentinel of `Loader::delete_sentinel`. Returns whether it is removed.
+    fn delete_entry(&mut self, key: &str) -> Result<bool> {
+        let sentinel = match &self.loader.delete_sentinel {
+            Some(sentinel) => sentinel.clone(),
+            None => return Ok(false),
+        };
+        self.enter(key)?;
+        let path = self.path();
+        let deleted = self.lookup(&path)?.as_deref() == Some(sentinel.as_str());
+        if deleted {
+            self.paths.insert(path.clone());
+            remove_entry(&mut self.value, &self.curpath[1..]);
+            self.modified = true;
+            self.applied.push(Applied {
+                name: path,
+                path: self.curpath[1..].to_vec(),
+                value: Value::String(sentinel),
+                secret: self.loader.is_secret(&self.field_path()),
+                operation: Operation::Delete,
+            });
+        }
+        self.exit();
+        Ok(deleted)
     }
 
-    fn serialize_f64(self, _: f64) -> Result<()> {
-        self.load()
-    }
+    /// Create the entries of the current map found in variable names,
+    /// if enabled by `Loader::dynamic_map`, and load them.
+    fn discover(&mut self) -> Result<()> {
+        if self.probe_only {
+            return Ok(());
+        }
+        let template = match self.loader.dynamic_maps.get(&self.field_path()) {
+            Some(template) => template.clone(),
+            None => return Ok(()),
+        };
+        let existing = match lookup(&self.value, &self.curpath[1..]) {
+            Some(Value::Mapping(map)) => map.clone(),
+            _ => return Ok(()),
+        };
+        self.curpath.push(String::new());
+        let prefix = self.path();
+        self.curpath.pop();
 
-    fn serialize_char(self, _: char) -> Result<()> {
-        self.load()
-    }
+        // The longest field name wins, so that `WEB_TLS_CERT` is `tls.cert` of `web`
+        // rather than `cert` of `web_tls`.
+        let mut leaves = Vec::new();
+        leaf_paths(&template, &mut Vec::new(), &mut leaves);
+        let mut suffixes: Vec<_> = leaves
+            .iter()
+            .map(|leaf| format!("_{}", self.loader.var_name(leaf)))
+            .collect();
+        suffixes.sort_by_key(|suffix| std::cmp::Reverse(suffix.len()));
+
+        let mut keys = BTreeSet::new();
+        for name in self.loader.source.names() {
+            let rest = match name.strip_prefix(prefix.as_str()) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let key = suffixes
+                .iter()
+                .find_map(|suffix| rest.strip_suffix(suffix.as_str()))
+                .filter(|key| !key.is_empty())
+                .map(str::to_lowercase);
+            if let Some(key) = key {
+                if !existing.contains_key(&Value::String(key.clone())) {
+                    keys.insert(key);
+                }
+            }
+        }
 
-    fn serialize_str(self, _: &str) -> Result<()> {
-        self.load()
+        for key in keys {
+            if let Some(Value::Mapping(map)) = lookup_mut(&mut self.value, &self.curpath[1..]) {
+                map.insert(key.clone().into(), template.clone());
+                self.modified = true;
+            }
+            self.child_value(&key, &template)?;
+        }
+        Ok(())
     }
 
-    fn serialize_bytes(self, _: &[u8]) -> Result<()> {
-        self.load()
-    }
+    /// Insert the unused variables under the prefix into the map set by `Loader::catch_all`.
+    fn catch_all(&mut self) -> Result<()> {
+        let path = match &self.loader.catch_all {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+        let segments: Vec<String> = path.split('.').map(String::from).collect();
+        let prefix = self
+            .loader
+            .var_name(&[self.curpath[0].clone(), String::new()]);
+
+        let mut entries = Vec::new();
+        for warning in self.unused() {
+            if let Warning::UnusedVar(name) = warning {
+                let key = match name.strip_prefix(prefix.as_str()) {
+                    Some(key) => key.to_string(),
+                    None => continue,
+                };
+                if let Some(val) = self.lookup(&name)? {
+                    entries.push((name, key, val));
+                }
+            }
+        }
 
-    fn serialize_none(self) -> Result<()> {
-        self.load()
+        let node = lookup_mut(&mut self.value, &segments)
+            .ok_or_else(|| Error::PackError(format!("catch-all field {} not found", path)))?;
+        if node.is_null() {
+            *node = Value::Mapping(Default::default());
+            self.modified = true;
+        }
+        let map = match node {
+            Value::Mapping(map) => map,
+            _ => {
+                return Err(Error::PackError(format!(
+                    "catch-all field {} is not a map",
+                    path
+                )))
+            }
+        };
+        for (name, key, val) in entries {
+            map.insert(key.clone().into(), Value::String(val.clone()));
+            self.modified = true;
+            let mut path = segments.clone();
+            path.push(key);
+            self.paths.insert(name.clone());
+            self.applied.push(Applied {
+                name,
+                path,
+                value: Value::String(val),
+                secret: false,
+                operation: Operation::SetRaw,
+            });
+        }
+        Ok(())
     }
 
-    fn serialize_some<T>(self, _: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        self.load()
+    /// Walk `t` again for each prefix listed in the include variable set by `Loader::include_var`,
+    /// depth first, so that the variables of included prefixes take precedence.
+    ///
+    /// Each prefix is walked at most once, which ends include loops.
+    fn include<T: ?Sized + Serialize>(&mut self, t: &T) -> Result<()> {
+        let suffix = match &self.loader.include_var {
+            Some(suffix) => suffix.clone(),
+            None => return Ok(()),
+        };
+        let root = self.curpath[0].clone();
+        let mut seen: HashSet<String> = HashSet::new();
+        seen.insert(self.loader.var_name(std::slice::from_ref(&root)));
+        let mut stack = vec![root.clone()];
+        while let Some(prefix) = stack.pop() {
+            if prefix != root {
+                self.curpath = vec![prefix.clone()];
+                self.included = true;
+                let r = t.serialize(&mut *self);
+                self.included = false;
+                r?;
+            }
+            let name = self.loader.var_name(&[prefix, suffix.clone()]);
+            self.paths.insert(name.clone());
+            let list = self.lookup(&name)?.unwrap_or_default();
+            let next: Vec<_> = list
+                .split(',')
+                .map(str::trim)
+                .filter(|prefix| {
+                    !prefix.is_empty() && seen.insert(self.loader.var_name(&[prefix.to_string()]))
+                })
+                .map(String::from)
+                .collect();
+            stack.extend(next.into_iter().rev());
+        }
+        self.curpath = vec![root];
+        Ok(())
     }
 
-    fn serialize_unit(self) -> Result<()> {
-        self.load()
+    /// Apply the assignments in the variable set by `Loader::assignments_var`,
+    /// e.g. `PFX_SET=http.port=8080;db.hosts[0]=x`, in order.
+    fn assign(&mut self) -> Result<()> {
+        let suffix = match &self.loader.assignments_var {
+            Some(suffix) => suffix.clone(),
+            None => return Ok(()),
+        };
+        let name = self.loader.var_name(&[self.curpath[0].clone(), suffix]);
+        self.paths.insert(name.clone());
+        let list = match self.var(&name)? {
+            Some(list) => list,
+            None => return Ok(()),
+        };
+        for assignment in list.split(';').map(str::trim).filter(|a| !a.is_empty()) {
+            let r = self.try_assign(&name, assignment);
+            self.recover(r)?;
+        }
+        Ok(())
     }
 
-    fn serialize_unit_struct(self, _: &'static str) -> Result<()> {
-        self.load()
-    }
+    fn try_assign(&mut self, name: &str, assignment: &str) -> Result<()> {
+        let invalid = || Error::UnpackError(name.into(), assignment.into());
+        let (path, val) = assignment.split_once('=').ok_or_else(invalid)?;
+        let (path, val) = (path.trim(), val.trim());
+        let segments = assignment_path(path).ok_or_else(invalid)?;
+        if val.is_empty() {
+            return Err(invalid());
+        }
 
-    fn serialize_unit_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-    ) -> Result<()> {
-        self.load()
+        let value = self.parse(None, name, val.into())?;
+        if !find_and_update(&mut self.value, &segments, &value) {
+            return Err(Error::UnpackError(
+                name.into(),
+                format!("{} not found", path),
+            ));
+        }
+        self.modified = true;
+        let secret = self.loader.is_secret(&segments.join("."));
+        self.applied.push(Applied {
+            name: name.into(),
+            path: segments,
+            value,
+            secret,
+            operation: Operation::Assign(path.into()),
+        });
+        Ok(())
     }
 
-    fn serialize_newtype_struct<T>(self, _: &'static str, _: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        self.load()
-    }
+    /// Read the negation of the bool variable `path` set by `Loader::negation_prefix`,
+    /// e.g. `PFX_NO_TLS` for `PFX_TLS`, returning its name and `false` if it is truthy.
+    ///
+    /// The negation is ignored with a warning if `set`, i.e. `path` is set as well.
+    fn read_negated(&mut self, path: &str, set: bool) -> Result<Option<(String, Value)>> {
+        let negation = match &self.loader.negation_prefix {
+            Some(negation) => negation,
+            None => return Ok(None),
+        };
+        let prefix = self.loader.var_name(&self.curpath[..1]);
+        let name = match path
+            .strip_prefix(prefix.as_str())
+            .and_then(|rest| rest.strip_prefix('_'))
+        {
+            Some(rest) => format!("{}_{}{}", prefix, negation, rest),
+            None => format!("{}{}", negation, path),
+        };
+        self.paths.insert(name.clone());
 
-    fn serialize_newtype_variant<T>(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
-    ) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        self.load()
+        let val = match self.lookup(&name)? {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        if set {
+            self.warnings
+                .push(Warning::NegationConflict(path.into(), name));
+            return Ok(None);
+        }
+        match val.trim().to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(Some((name, Value::Bool(false)))),
+            "" | "0" | "false" | "no" | "off" => Ok(None),
+            _ => Err(Error::UnpackError(name, val)),
+        }
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        self.load()?;
-        Ok(self)
-    }
+    /// Read the file named by the `FILE` variable of the current field, if set.
+    ///
+    /// A single trailing newline is removed from the contents.
+    fn read_file(&mut self) -> Result<Option<String>> {
+        self.curpath.push("file".into());
+        let path = self.path();
+        let file = self.var(&path);
+        self.curpath.pop();
+        self.paths.insert(path.clone());
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        self.load()?;
-        Ok(self)
+        let file = match file? {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        let contents = std::fs::read_to_string(&file)
+            .map_err(|e| Error::IoError(format!("{} ({}): {}", path, file, e)))?;
+        let contents = contents.strip_suffix('\n').unwrap_or(&contents);
+        let contents = contents.strip_suffix('\r').unwrap_or(contents);
+        Ok(Some(contents.to_string()))
     }
 
-    fn serialize_tuple_struct(
-        self,
-        _name: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleStruct> {
-        self.load()?;
-        Ok(self)
+    /// Get the variable `name` from the source, ignoring case if configured.
+    ///
+    /// If several variables match ignoring case, the exact match or else
+    /// the first in sorted order is taken, with a `Warning::CaseCollision`.
+    fn lookup(&mut self, name: &str) -> Result<Option<String>> {
+        if self.probe_only || !self.loader.is_allowed(name) {
+            return Ok(None);
+        }
+        if !self.loader.case_insensitive {
+            return self.loader.source.var(name);
+        }
+        let source = &self.loader.source;
+        let names = self.names.get_or_insert_with(|| {
+            let mut names = source.names();
+            names.sort();
+            names
+        });
+        let found: Vec<String> = names
+            .iter()
+            .filter(|n| n.eq_ignore_ascii_case(name))
+            .cloned()
+            .collect();
+        let chosen = match found.iter().find(|n| *n == name).or_else(|| found.first()) {
+            Some(chosen) => chosen.clone(),
+            None => return Ok(None),
+        };
+        if found.len() > 1 {
+            self.warnings
+                .push(Warning::CaseCollision(name.into(), found));
+        }
+        self.loader.source.var(&chosen)
     }
 
-    fn serialize_tuple_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeTupleVariant> {
-        self.load()?;
-        Ok(self)
-    }
+    /// Read the variable `path` of the current field, whose kind is `kind` if known.
+    ///
+    /// Returns `None` if the variable is not set or ignored.
+    fn read(&mut self, path: &str, kind: Option<FieldKind>) -> Result<Option<Value>> {
+        let val = match self.var(path)? {
+            Some(val) => val,
+            None => match self.read_file()? {
+                Some(val) => val,
+                None => return Ok(None),
+            },
+        };
+        if self.loader.null_sentinel.as_deref() == Some(val.as_str()) {
+            return Ok(Some(Value::Null));
+        }
+        let val = match &self.loader.resolver {
+            Some(resolve) => resolve(path, val).map_err(|e| Error::UnpackError(path.into(), e))?,
+            None => val,
+        };
+        let val = match self.loader.ref_syntax {
+            Some(syntax) => expand::expand(&val, syntax, |name| self.loader.source.var(name))?,
+            None => val,
+        };
+        let val = match self.loader.transforms.get(&self.field_path()) {
+            Some(transform) => transform(val),
+            None if self.loader.trim => val.trim().to_string(),
+            None => val,
+        };
+        let val = match val.strip_prefix("b64:") {
+            Some(encoded) if self.loader.base64_values => encoding::decode_base64(encoded.trim())
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or_else(|| Error::UnpackError(path.into(), "invalid base64 value".into()))?,
+            _ => val,
+        };
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        self.load()?;
-        Ok(self)
+        if !val.is_empty() {
+            let value = self.parse(kind, path, val)?;
+            return match self.loader.max_value_nodes {
+                Some(max) if count_nodes(&value) > max => Err(Error::ValueTooLarge(path.into())),
+                _ => Ok(Some(value)),
+            };
+        }
+        match self.loader.empty {
+            EmptyPolicy::Null => {
+                self.warnings.push(Warning::EmptyCoerced(path.into()));
+                Ok(Some(Value::Null))
+            }
+            EmptyPolicy::Strict if kind == Some(FieldKind::Option) => Ok(Some(Value::Null)),
+            EmptyPolicy::Strict => Err(Error::UnpackError(
+                path.into(),
+                "empty value not allowed for non-optional field".into(),
+            )),
+            EmptyPolicy::Literal => Ok(Some(Value::String(val))),
+            EmptyPolicy::Ignore => Ok(None),
+        }
     }
 
-    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
-        Ok(self)
-    }
+    /// Check the elements of the current sequence, if set by its variable,
+    /// have the kind of the elements of the base sequence.
+    ///
+    /// Nothing is checked if the base sequence is empty.
+    fn check_elements(&mut self) -> Result<()> {
+        let path = self.path();
+        if !self.applied.iter().any(|applied| applied.name == path) {
+            return Ok(());
+        }
+        let mut first = self.curpath[1..].to_vec();
+        first.push("0".into());
+        let kind = match self.probes.iter().find(|probe| probe.path == first) {
+            Some(probe) => probe.kind,
+            None => return Ok(()),
+        };
 
-    fn serialize_struct_variant(
-        self,
-        _name: &'static str,
-        _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
-    ) -> Result<Self::SerializeStructVariant> {
-        self.load()?;
-        Ok(self)
+        let mut r = Ok(());
+        if let Some(Value::Sequence(seq)) = lookup(&self.value, &self.curpath[1..]) {
+            let bad = seq.iter().enumerate().find(|(_, v)| match kind {
+                FieldKind::Bool => !v.is_bool(),
+                FieldKind::Int => !v.is_i64() && !v.is_u64(),
+                FieldKind::Float => !v.is_number(),
+                FieldKind::Char | FieldKind::Str => !v.is_string(),
+                _ => false,
+            });
+            if let Some((i, v)) = bad {
+                r = Err(Error::UnpackError(
+                    format!("{}[{}]", path, i),
+                    describe::display(v),
+                ));
+            }
+        }
+        self.recover(r)
     }
-}
 
-impl<'a> ser::SerializeSeq for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
+    /// Decode the hex string set to the current field into the bytes, e.g. `PFX_KEY=deadbeef`,
+    /// if the field is a byte array or a sequence of `u8`. Tuples must get `len` bytes.
+    fn decode_bytes(&mut self, kind: FieldKind, len: Option<usize>) -> Result<()> {
+        let mut first = self.curpath[1..].to_vec();
+        first.push("0".into());
+        if kind != FieldKind::Bytes && !self.bytes.contains(&first) {
+            return Ok(());
+        }
 
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
-        Ok(())
+        let path = self.path();
+        let r = match lookup_mut(&mut self.value, &self.curpath[1..]) {
+            Some(value) if value.is_string() => {
+                let s = value.as_str().unwrap_or_default();
+                match (encoding::decode_hex(s), len) {
+                    (Some(bytes), Some(len)) if bytes.len() != len => Err(Error::UnpackError(
+                        path,
+                        format!("expected {} bytes, found {}", len, bytes.len()),
+                    )),
+                    (Some(bytes), _) => {
+                        *value = Value::Sequence(bytes.into_iter().map(Value::from).collect());
+                        Ok(())
+                    }
+                    (None, _) => Err(Error::UnpackError(path, s.into())),
+                }
+            }
+            _ => Ok(()),
+        };
+        self.recover(r)
     }
 
-    fn end(self) -> Result<()> {
-        Ok(())
+    /// Push the value of the `APPEND` variable to the current sequence.
+    fn append(&mut self) -> Result<()> {
+        if !self.in_scope() {
+            return Ok(());
+        }
+        let r = self.try_append();
+        self.recover(r)
     }
-}
 
-impl<'a> ser::SerializeTuple for &'a mut Serializer {
-    type Ok = ();
-    type Error = Error;
+    fn try_append(&mut self) -> Result<()> {
+        self.curpath.push(self.loader.append_suffix.clone());
+        let path = self.path();
+        let val = self.var(&path);
+        self.curpath.pop();
+        self.paths.insert(path.clone());
 
-    fn serialize_element<T>(&mut self, _value: &T) -> Result<()>
-    where
-        T: ?Sized + Serialize,
-    {
+        let val = match val? {
+            Some(val) if !val.is_empty() => self.parse(None, &path, val)?,
+            _ => return Ok(()),
+        };
+        if let Some(Value::Sequence(seq)) = lookup_mut(&mut self.value, &self.curpath[1..]) {
+            seq.push(val.clone());
+            self.modified = true;
+            let seq = Value::Sequence(seq.clone());
+            self.applied.push(Applied {
+                name: path,
+                path: self.curpath[1..].to_vec(),
+                value: val,
+                secret: self.loader.is_secret(&self.field_path()),
+                operation: Operation::Append(seq),
+            });
+        }
         Ok(())
     }
 
-    fn end(self) -> Result<()> {
+    /// Truncate the current sequence to the length given by the `LEN` variable.
+    fn truncate(&mut self) -> Result<()> {
+        if !self.in_scope() {
+            return Ok(());
+        }
+        let r = self.try_truncate();
+        self.recover(r)
+    }
+
+    fn try_truncate(&mut self) -> Result<()> {
+        self.curpath.push("len".into());
+        let path = self.path();
+        let len = self.var(&path);
+        self.curpath.pop();
+        self.paths.insert(path.clone());
+
+        let len = match len? {
+            Some(len) => len
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| Error::UnpackError(path.clone(), len))?,
+            None => return Ok(()),
+        };
+        if let Some(Value::Sequence(seq)) = lookup_mut(&mut self.value, &self.curpath[1..]) {
+            seq.truncate(len);
+            self.modified = true;
+            let seq = Value::Sequence(seq.clone());
+            self.applied.push(Applied {
+                name: path,
+                path: self.curpath[1..].to_vec(),
+                value: Value::from(len),
+                secret: self.loader.is_secret(&self.field_path()),
+                operation: Operation::Truncate(seq),
+            });
+        }
         Ok(())
     }
+
+    /// Parse the delimited entries `val` of a map, e.g. `a=b;c=d`.
+    fn parse_entries(&self, path: &str, val: &str, delim: &str) -> Result<Value> {
+        let mut map = serde_yaml::Mapping::new();
+        for entry in val.split(delim).filter(|e| !e.trim().is_empty()) {
+            let (key, value) = entry
+                .split_once(self.loader.map_kv_delimiter.as_str())
+                .ok_or_else(|| Error::UnpackError(path.into(), entry.into()))?;
+            let value = match value.trim() {
+                "" => Value::String(String::new()),
+                v => serde_yaml::from_str(v)
+                    .map_err(|e| Error::ValueParse(path.into(), e.to_string()))?,
+            };
+            map.insert(Value::String(key.trim().into()), value);
+        }
+        Ok(Value::Mapping(map))
+    }
+
+    /// Parse the non-empty value `val` of the variable `path`.
+    fn parse(&self, kind: Option<FieldKind>, path: &str, val: String) -> Result<Value> {
+        match self.loader.coercions.get(&self.field_path()) {
+            Some(Coercion::RawString) => return Ok(Value::String(val)),
+            Some(Coercion::Path) => {
+                let path = expand::expand_path(&val, |name| self.loader.source.var(name))?;
+                return Ok(Value::String(path));
+            }
+            None => {}
+        }
+        if let Some((truthy, falsy)) = self.loader.bool_tokens.get(&self.field_path()) {
+            let is = |words: &[String]| words.iter().any(|w| w.eq_ignore_ascii_case(&val));
+            if kind == Some(FieldKind::Bool) && is(truthy) {
+                return Ok(Value::Bool(true));
+            }
+            if kind == Some(FieldKind::Bool) && is(falsy) {
+                return Ok(Value::Bool(false));
+            }
+        }
+        #[cfg(feature = "duration")]
+        if self.duration && val.starts_with('P') {
+            return duration::parse_iso8601(&val).ok_or(Error::UnpackError(path.into(), val));
+        }
+        // Multiline strings such as PEM certificates are not YAML.
+        if kind == Some(FieldKind::Str) && val.contains('\n') {
+            return Ok(Value::String(val));
+        }
+        if let Some(delim) = &self.loader.map_entry_delimiter {
+            if kind == Some(FieldKind::Map) && !val.trim_start().starts_with('{') {
+                return self.parse_entries(path, &val, delim);
+            }
+        }
+
+        let int = kind == Some(FieldKind::Int);
+        let val = match self.loader.int_grouping {
+            Some(sep) if int && val.contains(sep) => {
+                let digits = val.replace(sep, "");
+                let unsigned = digits.strip_prefix('-').unwrap_or(&digits);
+                if unsigned.is_empty() || !unsigned.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(Error::UnpackError(path.into(), val));
+                }
+                digits
+            }
+            _ => val,
+        };
+        let value = match parse_radix_int(&val) {
+            Some(Ok(n)) if int => Ok(n),
+            Some(Err(())) if int => Err(Error::UnpackError(path.into(), val.clone())),
+            _ => match self.loader.format {
+                Format::Yaml => serde_yaml::from_str(&val)
+                    .map_err(|e| Error::ValueParse(path.into(), e.to_string())),
+                Format::Json => match serde_json::from_str(&val) {
+                    Ok(v) => Ok(v),
+                    // Strings are accepted without quotes.
+                    Err(_)
+                        if matches!(
+                            kind,
+                            Some(FieldKind::Str) | Some(FieldKind::Char) | Some(FieldKind::Option)
+                        ) =>
+                    {
+                        Ok(Value::String(val.clone()))
+                    }
+                    Err(e) => Err(Error::ValueParse(path.into(), e.to_string())),
+                },
+            },
+        }?;
+
+        // Integers never come from other tokens such as `true` or `1.5`.
+        if int && !value.is_i64() && !value.is_u64() {
+            return Err(Error::UnpackError(path.into(), val));
+        }
+        // Hex strings for bytes, such as `12345678`, are decoded once the elements are known.
+        let seq = matches!(
+            kind,
+            Some(FieldKind::Bytes) | Some(FieldKind::Seq) | Some(FieldKind::Tuple)
+        );
+        if seq && (value.is_number() || value.is_bool()) {
+            return Ok(Value::String(val));
+        }
+        Ok(value)
+    }
+
+    /// Walk the next element `value` of the current sequence or tuple.
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let index = self.indices.last_mut().expect("not in sequence");
+        let name = index.to_string();
+        *index += 1;
+
+        self.child(&name, value)
+    }
+
+    /// Check no variable sets an element of the current tuple beyond its length `len`.
+    fn check_indices(&mut self, len: usize) -> Result<()> {
+        if self.probe_only || !self.in_scope() {
+            return Ok(());
+        }
+        self.curpath.push(String::new());
+        let prefix = self.path();
+        self.curpath.pop();
+
+        let mut names = self.loader.source.names();
+        names.sort();
+        let r = match names.into_iter().find(|name| {
+            name.strip_prefix(prefix.as_str())
+                .and_then(|index| index.parse::<usize>().ok())
+                .is_some_and(|index| index >= len)
+        }) {
+            Some(name) => Err(Error::UnpackError(
+                name,
+                format!("index out of range for {} elements", len),
+            )),
+            None => Ok(()),
+        };
+        self.recover(r)
+    }
+
+    /// Check the current value is a sequence of `len` elements if it is a sequence.
+    fn check_len(&mut self, len: usize) -> Result<()> {
+        let r = match lookup(&self.value, &self.curpath[1..]) {
+            Some(Value::Sequence(seq)) if seq.len() != len => Err(Error::UnpackError(
+                self.path(),
+                format!("expected {} elements, found {}", len, seq.len()),
+            )),
+            _ => Ok(()),
+        };
+        self.recover(r)
+    }
+
+    fn unused(&self) -> Vec<Warning> {
+        // Names of the variables under the prefix start with the name of an empty field.
+        let scopes: Vec<(String, String)> = if self.loader.scopes.is_empty() {
+            let prefix = self
+                .loader
+                .var_name(&[self.curpath[0].clone(), String::new()]);
+            let mut scopes = vec![(prefix.clone(), prefix)];
+            for prefix in self.loader.field_prefixes.values() {
+                scopes.push((
+                    self.loader.var_name(std::slice::from_ref(prefix)),
+                    self.loader.var_name(&[prefix.clone(), String::new()]),
+                ));
+            }
+            scopes
+        } else {
+            self.loader
+                .scopes
+                .iter()
+                .map(|scope| {
+                    let mut segments = vec![self.curpath[0].clone()];
+                    segments.extend(scope.split('.').map(String::from));
+                    let name = self.loader.path_name(&segments);
+                    segments.push(String::new());
+                    (name, self.loader.path_name(&segments))
+                })
+                .collect()
+        };
+        let mut unused: Vec<_> = self
+            .loader
+            .source
+            .names()
+            .into_iter()
+            .filter(|name| {
+                scopes
+                    .iter()
+                    .any(|(scope, prefix)| name == scope || name.starts_with(prefix))
+                    && !self.paths.contains(name)
+                    && *name != self.loader.overlay_var_name()
+                    && !(self.loader.case_insensitive
+                        && self.paths.iter().any(|p| p.eq_ignore_ascii_case(name)))
+            })
+            .collect();
+        unused.sort();
+        unused.into_iter().map(Warning::UnusedVar).collect()
+    }
+
+    fn finish(mut self) -> (Value, Report) {
+        let unused = self.unused();
+        self.warnings.extend(unused);
+        let report = Report {
+            applied: self.applied,
+            checked: self.probes.into_iter().map(|p| p.name).collect(),
+            warnings: self.warnings,
+        };
+        if self.loader.log_summary {
+            log::debug!(
+                "loaded environment variables {}: {}",
+                self.loader.var_name(&self.curpath[..1]),
+                report.summary()
+            );
+        }
+        (self.value, report)
+    }
 }
 
-// Same thing but for tuple structs.
-impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+impl ser::Serializer for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
 
-    fn serialize_field<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_bool(self, _: bool) -> Result<()> {
+        self.load(FieldKind::Bool)
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<()> {
+        self.bytes.insert(self.curpath[1..].to_vec());
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<()> {
+        self.load(FieldKind::Int)
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<()> {
+        self.load(FieldKind::Float)
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<()> {
+        self.load(FieldKind::Float)
+    }
+
+    fn serialize_char(self, _: char) -> Result<()> {
+        self.load(FieldKind::Char)
+    }
+
+    fn serialize_str(self, _: &str) -> Result<()> {
+        self.load(FieldKind::Str)
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<()> {
+        self.load(FieldKind::Bytes)?;
+        self.decode_bytes(FieldKind::Bytes, None)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.load(FieldKind::Option)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        self.load(FieldKind::Option)?;
+        // Walk the inner value for its elements or fields, which are at the same path.
+        self.inner = true;
+        let r = value.serialize(&mut *self);
+        self.inner = false;
+        r
+    }
+
+    // Units, including `PhantomData`, have nothing to override.
+    fn serialize_unit(self) -> Result<()> {
         Ok(())
     }
 
-    fn end(self) -> Result<()> {
+    fn serialize_unit_struct(self, _: &'static str) -> Result<()> {
         Ok(())
     }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.load(FieldKind::Enum)
+    }
+
+    fn serialize_newtype_struct<T>(self, _: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    // The value is located under the variant name like the fields of struct variants,
+    // as externally tagged enums are represented as `{variant: value}`.
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.load(FieldKind::Enum)?;
+        self.variants.push(variant);
+        let r = self.child(variant, value);
+        self.variants.pop();
+        r
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        self.load(FieldKind::Seq)?;
+        self.indices.push(0);
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.load(FieldKind::Tuple)?;
+        self.check_len(len)?;
+        self.indices.push(0);
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.load(FieldKind::Tuple)?;
+        self.check_len(len)?;
+        self.indices.push(0);
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.load(FieldKind::Enum)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        // Structs with flattened fields are serialized as maps of unknown length,
+        // and are loaded as structs so that the variable is merged.
+        if len.is_some() {
+            self.load(FieldKind::Map)?;
+        } else if self.curpath.len() > 1 || self.loader.root_var {
+            self.load(FieldKind::Struct)?;
+        }
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        // The whole struct is applied before its fields, so the more specific variables win.
+        // The root struct is loaded only if enabled.
+        if self.curpath.len() > 1 || self.loader.root_var {
+            #[cfg(feature = "duration")]
+            {
+                self.duration = _name == "Duration" && _len == 2;
+            }
+            let r = self.load(FieldKind::Struct);
+            #[cfg(feature = "duration")]
+            {
+                self.duration = false;
+            }
+            r?;
+        }
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.load(FieldKind::Enum)?;
+        self.enter(variant)?;
+        self.variants.push(variant);
+        Ok(self)
+    }
+}
+
+// Elements are located by their indices, e.g. `PFX_LIST_0`,
+// after the whole sequence is loaded from `PFX_LIST`.
+// Then the sequence is truncated to the length given by `PFX_LIST_LEN`.
+impl ser::SerializeSeq for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.indices.pop();
+        self.decode_bytes(FieldKind::Seq, None)?;
+        self.check_elements()?;
+        self.append()?;
+        self.truncate()
+    }
+}
+
+// Elements are located by their indices as for sequences, after the whole tuple is loaded.
+// Indices beyond the arity are errors.
+impl ser::SerializeTuple for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        let len = self.indices.pop().expect("not in tuple");
+        self.decode_bytes(FieldKind::Tuple, Some(len))?;
+        self.check_indices(len)
+    }
+}
+
+// Same thing but for tuple structs.
+impl ser::SerializeTupleStruct for &mut Serializer<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        let len = self.indices.pop().expect("not in tuple");
+        self.decode_bytes(FieldKind::Tuple, Some(len))?;
+        self.check_indices(len)
+    }
 }
 
-impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+impl ser::SerializeTupleVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -343,30 +1578,38 @@ impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeMap for &'a mut Serializer {
+// Entries with string keys are located by their keys, e.g. `PFX_MAP_WEB`,
+// after the whole map is loaded from `PFX_MAP`.
+// Then the new entries are discovered from variable names if enabled.
+impl ser::SerializeMap for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_key<T>(&mut self, _key: &T) -> Result<()>
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
+        self.key = serde_yaml::to_value(key)?.as_str().map(String::from);
         Ok(())
     }
 
-    fn serialize_value<T>(&mut self, _value: &T) -> Result<()>
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Ok(())
+        match self.key.take() {
+            Some(key) if self.delete_entry(&key)? => Ok(()),
+            Some(key) => self.child(&key, value),
+            None => Ok(()),
+        }
     }
 
     fn end(self) -> Result<()> {
-        Ok(())
+        self.discover()
     }
 }
 
-impl<'a> ser::SerializeStruct for &'a mut Serializer {
+impl ser::SerializeStruct for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
@@ -374,10 +1617,7 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     where
         T: ?Sized + Serialize,
     {
-        self.enter(key);
-        value.serialize(&mut **self)?;
-        self.exit();
-        Ok(())
+        self.child(key, value)
     }
 
     fn end(self) -> Result<()> {
@@ -385,27 +1625,145 @@ impl<'a> ser::SerializeStruct for &'a mut Serializer {
     }
 }
 
-impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+// Fields of struct variants are located under the variant name
+// as externally tagged enums are represented as `{variant: {fields}}`.
+// Overriding them fails if the variant is no longer selected in the value.
+impl ser::SerializeStructVariant for &mut Serializer<'_> {
     type Ok = ();
     type Error = Error;
 
-    fn serialize_field<T>(&mut self, _key: &'static str, _value: &T) -> Result<()>
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
     where
         T: ?Sized + Serialize,
     {
-        Ok(())
+        self.child(key, value)
     }
 
     fn end(self) -> Result<()> {
+        self.variants.pop();
+        self.exit();
         Ok(())
     }
 }
 
 pub fn load<T: Serialize + DeserializeOwned>(pfx: &str, t: &T) -> Result<T> {
-    let value = serde_yaml::to_value(&t)?;
-    let mut ser = Serializer::new(pfx, value);
-    t.serialize(&mut ser)?;
-    Ok(serde_yaml::from_value(ser.value)?)
+    Loader::new(pfx).load(t)
+}
+
+/// Same as `load` but returns a clone of `t` if no environment variable changes it.
+pub fn load_cloned<T: Serialize + DeserializeOwned + Clone>(pfx: &str, t: &T) -> Result<T> {
+    Loader::new(pfx).load_cloned(t)
+}
+
+/// Same as `load` but loads `sub`, a part of a larger config, in place.
+pub fn load_sub<T: Serialize + DeserializeOwned>(pfx: &str, sub: &mut T) -> Result<()> {
+    Loader::new(pfx).load_sub(sub)
+}
+
+/// Prefix derived from the package name `name`, uppercased with `-` replaced by `_`,
+/// e.g. `MY_APP` for `my-app`.
+pub fn app_prefix(name: &str) -> String {
+    name.replace('-', "_").to_uppercase()
+}
+
+/// Same as `load` with the prefix derived from the name of the calling package by
+/// `app_prefix`, e.g. `MY_APP` in the package `my-app`.
+#[macro_export]
+macro_rules! load_app {
+    ($t:expr) => {
+        $crate::load(&$crate::app_prefix(env!("CARGO_PKG_NAME")), $t)
+    };
+}
+
+/// Same as `load` but reads the variables from `source`.
+pub fn load_from<T, S>(pfx: &str, t: &T, source: S) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+    S: VarSource + Send + Sync + 'static,
+{
+    Loader::new(pfx).source(source).load(t)
+}
+
+/// Same as `load` but also reads the variables from the `.env` files at `paths`,
+/// e.g. `&[".env", ".env.local", ".env.production"]`. Later files take precedence over
+/// earlier ones, and the environment variables of the process over all of them.
+///
+/// Missing files are errors if `required`, and skipped otherwise.
+pub fn load_from_dotenv_files<T, P>(pfx: &str, t: &T, paths: &[P], required: bool) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+    P: AsRef<std::path::Path>,
+{
+    let mut sources: Vec<Box<dyn VarSource + Send + Sync>> = vec![Box::new(ProcessEnv)];
+    for path in paths.iter().rev() {
+        let path = path.as_ref();
+        if !required && !path.exists() {
+            continue;
+        }
+        sources.push(Box::new(DotEnv::from_path(path)?));
+    }
+    Loader::new(pfx).source(sources).load(t)
+}
+
+/// Same as `load` but merges the YAML or JSON document read from `reader` into `t`
+/// before loading environment variables. Environment variables take precedence over the document.
+pub fn load_patch<T, R>(pfx: &str, t: &T, reader: R) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+    R: std::io::Read,
+{
+    Loader::new(pfx).load_patch(t, reader)
+}
+
+/// Same as `load_patch` but with the YAML or JSON file named by the variable `file_var`,
+/// e.g. `CONFIG_FILE=/etc/app.yaml`. Same as `load` if `file_var` is not set.
+pub fn load_with_file_var<T>(file_var: &str, pfx: &str, t: &T) -> Result<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    Loader::new(pfx).load_with_file_var(file_var, t)
+}
+
+/// Same as `load` but takes `t` by value and also returns the report of the load.
+pub fn load_with_report<T: Serialize + DeserializeOwned>(pfx: &str, t: T) -> Result<(T, Report)> {
+    Loader::new(pfx).load_with_report(t)
+}
+
+/// Same as `load` but skips the environment variables which fail to apply,
+/// returning their errors along with the partially loaded value.
+pub fn load_lenient<T: Serialize + DeserializeOwned + Clone>(pfx: &str, t: &T) -> (T, Vec<Error>) {
+    Loader::new(pfx).load_lenient(t)
+}
+
+/// Get the value of the single field at `path`, e.g. `&["http", "port"]` for `PFX_HTTP_PORT`.
+///
+/// Returns `None` if the environment variable is not set.
+pub fn get_field<V: DeserializeOwned>(pfx: &str, path: &[&str]) -> Result<Option<V>> {
+    Loader::new(pfx).get_field(path)
+}
+
+/// Same as `load` but returns the warnings instead of logging them.
+pub fn load_collect_warnings<T: Serialize + DeserializeOwned>(
+    pfx: &str,
+    t: &T,
+) -> Result<(T, Vec<Warning>)> {
+    Loader::new(pfx).load_collect_warnings(t)
+}
+
+/// Same as `load` but with the prefix read from the environment variable `prefix_var`,
+/// e.g. `APP_INSTANCE=tenant1` to load the `TENANT1_*` variables.
+///
+/// `default` is used if `prefix_var` is not set or empty.
+pub fn load_dynamic_prefix<T: Serialize + DeserializeOwned>(
+    prefix_var: &str,
+    default: &str,
+    t: &T,
+) -> Result<T> {
+    let pfx = ProcessEnv
+        .var(prefix_var)?
+        .filter(|pfx| !pfx.is_empty())
+        .unwrap_or_else(|| default.into());
+    load(&pfx, t)
 }
 
 #[cfg(test)]
@@ -413,13 +1771,25 @@ mod test {
     use super::*;
     use serde::Deserialize;
 
-    struct Vars {
+    /// Source of the variables `vars`, for tests not touching the process environment.
+    pub(crate) fn source(vars: &[(&str, &str)]) -> std::collections::HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// Variables set for a scope, removed on drop. The process environment is locked
+    /// while alive, as by `testing::ScopedVars`.
+    pub(crate) struct Vars {
         varset: Vec<String>,
+        _lock: crate::testing::EnvLock,
     }
 
     impl Vars {
-        fn new(varset: Vec<(String, String)>) -> Self {
+        pub(crate) fn new(varset: Vec<(String, String)>) -> Self {
+            let lock = crate::testing::lock_env();
             Self {
+                _lock: lock,
                 varset: varset
                     .into_iter()
                     .map(|(key, value)| {
@@ -434,17 +1804,19 @@ mod test {
     impl Drop for Vars {
         fn drop(&mut self) {
             for key in &self.varset {
-                std::env::remove_var(&key);
+                std::env::remove_var(key);
             }
         }
     }
 
     macro_rules! vars {
         ($($k:literal => $v:literal;)*) => {
-            Vars::new(vec![$(($k.into(), $v.into())),*])
+            crate::test::Vars::new(vec![$(($k.into(), $v.into())),*])
         }
     }
 
+    pub(crate) use vars;
+
     #[test]
     fn test_envs_simple() {
         #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
@@ -485,7 +1857,9 @@ mod test {
 
         let a = A::default();
 
+        let v = vars!();
         assert_eq!(load("pfx", &a).unwrap(), a);
+        drop(v);
         let _v = vars!(
             "PFX_B" => "32";
             "PFX_C_A" => "12";
@@ -556,8 +1930,8 @@ mod test {
             i2: -2,
             i3: -3,
             i4: 4,
-            f1: 3.14,
-            f2: -3.1415,
+            f1: 3.25,
+            f2: -3.125,
             s: "hello".into(),
             c: 'c',
             o1: None,
@@ -579,7 +1953,9 @@ mod test {
             dur: Duration::from_secs(39),
         };
 
+        let v = vars!();
         assert_eq!(load("pfx", &a).unwrap(), a);
+        drop(v);
         let _v = vars!(
             "PFX_A" => "true";
             "PFX_U1" => "4";
@@ -649,9 +2025,185 @@ mod test {
         a.sock = "10.32.0.33:4454".parse().unwrap();
         a.path = PathBuf::from("/home/biggus/dickus");
         a.dur = Duration::from_secs(30);
+        // Nested structs are set as a whole, so `30sec` is no longer ignored.
+        assert!(load("pfx", &a).is_err());
+
+        let _d = vars!(
+            "PFX_DUR" => "{secs: 30, nanos: 0}";
+        );
         assert_eq!(load("pfx", &a).unwrap(), a,);
     }
 
+    #[test]
+    fn test_envs_parent_then_child() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+            b: u32,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            c: C,
+        }
+
+        let _v = vars!(
+            "PRECEDENCE_C" => "{a: 1, b: 2}";
+            "PRECEDENCE_C_A" => "3";
+        );
+        assert_eq!(
+            load("precedence", &A::default()).unwrap(),
+            A {
+                c: C { a: 3, b: 2 }
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_struct_merge() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct D {
+            x: u32,
+            y: u32,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+            b: u32,
+            d: D,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            c: C,
+        }
+
+        let a = A {
+            c: C {
+                a: 1,
+                b: 2,
+                d: D { x: 3, y: 4 },
+            },
+        };
+
+        let v = vars!(
+            "MERGE_C" => "{a: 10, b: 20, d: {x: 30, y: 40}}";
+        );
+        assert_eq!(
+            load("merge", &a).unwrap(),
+            A {
+                c: C {
+                    a: 10,
+                    b: 20,
+                    d: D { x: 30, y: 40 },
+                },
+            }
+        );
+        drop(v);
+
+        let _v = vars!(
+            "MERGE_C" => "{a: 10, d: {y: 40}}";
+        );
+        assert_eq!(
+            load("merge", &a).unwrap(),
+            A {
+                c: C {
+                    a: 10,
+                    b: 2,
+                    d: D { x: 3, y: 40 },
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_root() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+            b: u32,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            name: String,
+            port: u16,
+            c: C,
+        }
+
+        let _v = vars!(
+            "ROOTVAR" => "{name: app, c: {a: 1, b: 2}}";
+            "ROOTVAR_C_B" => "3";
+        );
+        let a = A {
+            port: 80,
+            ..A::default()
+        };
+        assert_eq!(
+            load("rootvar", &a).unwrap(),
+            A {
+                port: 80,
+                c: C { a: 0, b: 3 },
+                ..A::default()
+            }
+        );
+        assert_eq!(
+            Loader::new("rootvar").root_var(true).load(&a).unwrap(),
+            A {
+                name: "app".into(),
+                port: 80,
+                c: C { a: 1, b: 3 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_file() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+            b: u32,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            c: C,
+            password: String,
+        }
+
+        let dir = std::env::temp_dir();
+        let c = dir.join(format!("eload-file-c-{}.yaml", std::process::id()));
+        let password = dir.join(format!("eload-file-password-{}", std::process::id()));
+        std::fs::write(&c, "a: 10\n").unwrap();
+        std::fs::write(&password, "hunter2\n").unwrap();
+
+        let a = A {
+            c: C { a: 1, b: 2 },
+            password: String::new(),
+        };
+        let v = Vars::new(vec![
+            ("FILE_C_FILE".into(), c.display().to_string()),
+            ("FILE_PASSWORD_FILE".into(), password.display().to_string()),
+        ]);
+        assert_eq!(
+            load("file", &a).unwrap(),
+            A {
+                c: C { a: 10, b: 2 },
+                password: "hunter2".into(),
+            }
+        );
+        drop(v);
+
+        let missing = dir.join("eload-file-missing.yaml");
+        let _v = Vars::new(vec![("FILE_C_FILE".into(), missing.display().to_string())]);
+        match load("file", &a) {
+            Err(Error::IoError(msg)) => {
+                assert!(msg.starts_with("FILE_C_FILE"));
+                assert!(msg.contains("eload-file-missing.yaml"));
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        std::fs::remove_file(c).unwrap();
+        std::fs::remove_file(password).unwrap();
+    }
+
     #[test]
     fn test_envs_mixed() {
         #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
@@ -670,7 +2222,9 @@ mod test {
 
         let a = A::default();
 
+        let v = vars!();
         assert_eq!(load("pfx", &a).unwrap(), a);
+        drop(v);
         let _v = vars!(
             "PFX_A" => "true";
             "PFX_B" => "33";
@@ -692,4 +2246,1575 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_warnings_ambiguous() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a_b: u32,
+            a: B,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            b: u32,
+        }
+
+        let a = A::default();
+
+        let (_, warnings) = load_collect_warnings("ambiguous", &a).unwrap();
+        assert_eq!(warnings, vec![Warning::Ambiguous("AMBIGUOUS_A_B".into())]);
+    }
+
+    #[test]
+    fn test_warnings_unused_and_empty() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: Option<u32>,
+        }
+
+        let a = A { a: Some(1) };
+
+        let _v = vars!(
+            "UNUSEDWARN_A" => "";
+            "UNUSEDWARN_B" => "1";
+        );
+        let (a, warnings) = load_collect_warnings("unusedwarn", &a).unwrap();
+        assert_eq!(a, A { a: None });
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::EmptyCoerced("UNUSEDWARN_A".into()),
+                Warning::UnusedVar("UNUSEDWARN_B".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_envs_internally_tagged() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            backend: Backend,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        #[serde(tag = "type", rename_all = "lowercase")]
+        enum Backend {
+            Postgres { host: String, port: u16 },
+            Mysql { host: String, port: u16 },
+        }
+
+        let a = A {
+            backend: Backend::Mysql {
+                host: "localhost".into(),
+                port: 3306,
+            },
+        };
+
+        assert_eq!(load("internal", &a).unwrap(), a);
+        let _v = vars!(
+            "INTERNAL_BACKEND_TYPE" => "postgres";
+            "INTERNAL_BACKEND_HOST" => "db.example.com";
+            "INTERNAL_BACKEND_PORT" => "5432";
+        );
+        assert_eq!(
+            load("internal", &a).unwrap(),
+            A {
+                backend: Backend::Postgres {
+                    host: "db.example.com".into(),
+                    port: 5432,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_struct_variant() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            backend: Backend,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        enum Backend {
+            Postgres { host: String, port: u16 },
+            Sqlite { path: String },
+        }
+
+        let a = A {
+            backend: Backend::Postgres {
+                host: "localhost".into(),
+                port: 5432,
+            },
+        };
+
+        let _v = vars!(
+            "EXTERNAL_BACKEND_POSTGRES_PORT" => "6543";
+        );
+        assert_eq!(
+            load("external", &a).unwrap(),
+            A {
+                backend: Backend::Postgres {
+                    host: "localhost".into(),
+                    port: 6543,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_flatten() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        #[serde(deny_unknown_fields)]
+        struct A {
+            name: String,
+            #[serde(flatten)]
+            http: Http,
+            db: Db,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        #[serde(deny_unknown_fields)]
+        struct Db {
+            host: String,
+            #[serde(flatten)]
+            pool: Pool,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Pool {
+            size: u32,
+            timeout: u32,
+        }
+
+        let a = A {
+            name: "a".into(),
+            http: Http { port: 80 },
+            db: Db {
+                host: "localhost".into(),
+                pool: Pool {
+                    size: 1,
+                    timeout: 10,
+                },
+            },
+        };
+
+        // Flattened fields are addressed as fields of the parent.
+        let v = vars!(
+            "FLATTEN_PORT" => "8080";
+            "FLATTEN_DB_SIZE" => "4";
+        );
+        assert_eq!(
+            load("flatten", &a).unwrap(),
+            A {
+                name: "a".into(),
+                http: Http { port: 8080 },
+                db: Db {
+                    host: "localhost".into(),
+                    pool: Pool {
+                        size: 4,
+                        timeout: 10,
+                    },
+                },
+            }
+        );
+        drop(v);
+
+        // Structs with flattened fields are merged like other structs.
+        let v = vars!(
+            "FLATTEN_DB" => "{host: db.local, timeout: 30}";
+        );
+        assert_eq!(
+            load("flatten", &a).unwrap().db,
+            Db {
+                host: "db.local".into(),
+                pool: Pool {
+                    size: 1,
+                    timeout: 30,
+                },
+            }
+        );
+        drop(v);
+
+        // Unknown fields are still denied.
+        let _v = vars!(
+            "FLATTEN_DB" => "{unknown: 1}";
+        );
+        assert!(matches!(load("flatten", &a), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn test_envs_enum_paths() {
+        #[derive(Serialize)]
+        struct A<E> {
+            e: E,
+        }
+        #[derive(Serialize)]
+        struct Inner {
+            b: u32,
+        }
+        #[derive(Serialize)]
+        enum External {
+            Unit,
+            Newtype(Inner),
+            Tuple(u32, u32),
+            Struct { a: u32 },
+        }
+        #[derive(Serialize)]
+        #[serde(tag = "type")]
+        enum Internal {
+            Unit,
+            Newtype(Inner),
+            Struct { a: u32 },
+        }
+        #[derive(Serialize)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Unit,
+            Newtype(u32),
+            Struct { a: u32 },
+        }
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Untagged {
+            Newtype(u32),
+            Struct { a: u32 },
+        }
+
+        fn names<E: Serialize>(e: E) -> Vec<String> {
+            describe("enum", &A { e })
+                .unwrap()
+                .into_iter()
+                .map(|doc| doc.name)
+                .collect()
+        }
+
+        // Externally tagged: the variant value is under the variant name.
+        assert_eq!(names(External::Unit), vec!["ENUM_E"]);
+        assert_eq!(
+            names(External::Newtype(Inner { b: 1 })),
+            vec!["ENUM_E", "ENUM_E_NEWTYPE", "ENUM_E_NEWTYPE_B"]
+        );
+        assert_eq!(names(External::Tuple(1, 2)), vec!["ENUM_E"]);
+        assert_eq!(
+            names(External::Struct { a: 1 }),
+            vec!["ENUM_E", "ENUM_E_STRUCT_A"]
+        );
+
+        // Internally tagged: the tag is a field beside the fields of the variant.
+        assert_eq!(names(Internal::Unit), vec!["ENUM_E", "ENUM_E_TYPE"]);
+        assert_eq!(
+            names(Internal::Newtype(Inner { b: 1 })),
+            vec!["ENUM_E", "ENUM_E_B", "ENUM_E_TYPE"]
+        );
+        assert_eq!(
+            names(Internal::Struct { a: 1 }),
+            vec!["ENUM_E", "ENUM_E_A", "ENUM_E_TYPE"]
+        );
+
+        // Adjacently tagged: the tag and the content are fields.
+        assert_eq!(names(Adjacent::Unit), vec!["ENUM_E", "ENUM_E_T"]);
+        assert_eq!(
+            names(Adjacent::Newtype(1)),
+            vec!["ENUM_E", "ENUM_E_C", "ENUM_E_T"]
+        );
+        assert_eq!(
+            names(Adjacent::Struct { a: 1 }),
+            vec!["ENUM_E", "ENUM_E_C", "ENUM_E_C_A", "ENUM_E_T"]
+        );
+
+        // Untagged: the variant value is the field itself.
+        assert_eq!(names(Untagged::Newtype(1)), vec!["ENUM_E"]);
+        assert_eq!(names(Untagged::Struct { a: 1 }), vec!["ENUM_E", "ENUM_E_A"]);
+    }
+
+    #[test]
+    fn test_envs_newtype_and_adjacent_variants() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            ext: External,
+            adj: Adjacent,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Inner {
+            b: u32,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        enum External {
+            Newtype(Inner),
+            Other(u32),
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Struct { a: u32 },
+            Newtype(u32),
+        }
+
+        let a = A {
+            ext: External::Newtype(Inner { b: 1 }),
+            adj: Adjacent::Struct { a: 1 },
+        };
+
+        let v = vars!(
+            "VARIANTS_EXT_NEWTYPE_B" => "2";
+            "VARIANTS_ADJ_C_A" => "3";
+        );
+        assert_eq!(
+            load("variants", &a).unwrap(),
+            A {
+                ext: External::Newtype(Inner { b: 2 }),
+                adj: Adjacent::Struct { a: 3 },
+            }
+        );
+        drop(v);
+
+        let _v = vars!(
+            "VARIANTS_EXT" => "{Other: 4}";
+            "VARIANTS_ADJ_T" => "Newtype";
+            "VARIANTS_ADJ_C" => "5";
+        );
+        assert_eq!(
+            load("variants", &a).unwrap(),
+            A {
+                ext: External::Other(4),
+                adj: Adjacent::Newtype(5),
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_unselected_variant() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            backend: Backend,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        enum Backend {
+            Postgres { host: String },
+            Mysql { host: String },
+        }
+
+        let a = A {
+            backend: Backend::Postgres {
+                host: "localhost".into(),
+            },
+        };
+
+        let _v = vars!(
+            "UNSELECTED_BACKEND" => "{Mysql: {host: mysql.local}}";
+            "UNSELECTED_BACKEND_POSTGRES_HOST" => "postgres.local";
+        );
+        match load("unselected", &a) {
+            Err(Error::UnpackError(path, msg)) => {
+                assert_eq!(path, "UNSELECTED_BACKEND_POSTGRES_HOST");
+                assert_eq!(msg, "variant Postgres is not selected");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_envs_radix_int() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            mask: u8,
+            mode: u32,
+            flags: u16,
+            offset: i32,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "RADIX_MASK" => "0xFF";
+            "RADIX_MODE" => "0o755";
+            "RADIX_FLAGS" => "0b1010";
+            "RADIX_OFFSET" => "-0x10";
+        );
+        assert_eq!(
+            load("radix", &a).unwrap(),
+            A {
+                mask: 255,
+                mode: 0o755,
+                flags: 10,
+                offset: -16,
+            }
+        );
+
+        let _v = vars!(
+            "RADIX_MODE" => "0o789";
+        );
+        match load("radix", &a) {
+            Err(Error::UnpackError(path, value)) => {
+                assert_eq!(path, "RADIX_MODE");
+                assert_eq!(value, "0o789");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_envs_int_rejects_other_tokens() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            u1: u8,
+        }
+
+        for raw in &["true", "yes", "1.5"] {
+            let _v = Vars::new(vec![("INTTOKEN_U1".into(), raw.to_string())]);
+            match load("inttoken", &A::default()) {
+                Err(Error::UnpackError(path, value)) => {
+                    assert_eq!(path, "INTTOKEN_U1");
+                    assert_eq!(value, *raw);
+                }
+                r => panic!("unexpected result: {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+            b: B,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+            b: C,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "DEPTH_B_B_A" => "3";
+        );
+        assert_eq!(
+            Loader::new("depth").max_depth(3).load(&a).unwrap(),
+            A {
+                a: 0,
+                b: B {
+                    a: 0,
+                    b: C { a: 3 },
+                },
+            }
+        );
+        match Loader::new("depth").max_depth(2).load(&a) {
+            Err(Error::DepthExceeded(2)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[cfg(feature = "duration")]
+    #[test]
+    fn test_envs_iso8601_duration() {
+        use std::time::Duration;
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            timeout: Duration,
+            interval: Duration,
+        }
+
+        let _v = vars!(
+            "ISODUR_TIMEOUT" => "PT2H";
+            "ISODUR_INTERVAL" => "PT30S";
+        );
+        assert_eq!(
+            load("isodur", &A::default()).unwrap(),
+            A {
+                timeout: Duration::from_secs(7200),
+                interval: Duration::from_secs(30),
+            }
+        );
+        drop(_v);
+
+        let _v = vars!(
+            "ISODUR_TIMEOUT" => "PT2X";
+        );
+        match load("isodur", &A::default()) {
+            Err(Error::UnpackError(path, value)) => {
+                assert_eq!(path, "ISODUR_TIMEOUT");
+                assert_eq!(value, "PT2X");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_max_value_nodes() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            v: Vec<u32>,
+            n: Vec<serde_yaml::Value>,
+        }
+
+        let a = A::default();
+        let loader = Loader::new("nodes").max_value_nodes(10);
+
+        let _v = vars!(
+            "NODES_V" => "[1, 2, 3]";
+        );
+        assert_eq!(loader.load(&a).unwrap().v, vec![1, 2, 3]);
+
+        let _v = vars!(
+            "NODES_N" => "[[[[[[[[[[[[1]]]]]]]]]]]]";
+        );
+        match loader.load(&a) {
+            Err(Error::ValueTooLarge(path)) => assert_eq!(path, "NODES_N"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_envs_underscore_prefix() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            host: String,
+            app: B,
+            my: C,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            app: B,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "MY_APP_HOST" => "a";
+            "MY_APP_APP_HOST" => "b";
+            "MY_APP_MY_APP_HOST" => "c";
+        );
+        assert_eq!(
+            load("my_app", &a).unwrap(),
+            A {
+                host: "a".into(),
+                app: B { host: "b".into() },
+                my: C {
+                    app: B { host: "c".into() },
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_load_patch() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+            b: B,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+            b: u32,
+            c: u32,
+        }
+
+        let a = A {
+            a: 1,
+            b: B { a: 1, b: 1, c: 1 },
+        };
+        let patch = "b:\n  a: 2\n  b: 2\n";
+
+        assert_eq!(
+            load_patch("patch", &a, patch.as_bytes()).unwrap(),
+            A {
+                a: 1,
+                b: B { a: 2, b: 2, c: 1 },
+            }
+        );
+
+        let _v = vars!(
+            "PATCH_B_B" => "3";
+        );
+        assert_eq!(
+            load_patch("patch", &a, patch.as_bytes()).unwrap(),
+            A {
+                a: 1,
+                b: B { a: 2, b: 3, c: 1 },
+            }
+        );
+        match load_patch("patch", &a, "{b: [".as_bytes()) {
+            Err(Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_coerce_raw_string() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            start: String,
+            year: String,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "RAWSTR_START" => "2024-01-02T03:04:05Z";
+            "RAWSTR_YEAR" => "2024";
+        );
+        match load("rawstr", &a) {
+            Err(Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        assert_eq!(
+            Loader::new("rawstr")
+                .coerce("start", Coercion::RawString)
+                .coerce("year", Coercion::RawString)
+                .load(&a)
+                .unwrap(),
+            A {
+                start: "2024-01-02T03:04:05Z".into(),
+                year: "2024".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_coerce_raw_string_from_str() {
+        use std::str::FromStr;
+
+        // Deserialized from strings like `semver::Version`.
+        #[derive(Debug, PartialEq, Eq)]
+        struct Version(u64, u64, u64);
+
+        impl FromStr for Version {
+            type Err = String;
+
+            fn from_str(s: &str) -> std::result::Result<Self, String> {
+                let parts: Vec<u64> = s
+                    .split('.')
+                    .map(|part| part.parse().map_err(|_| s.to_string()))
+                    .collect::<std::result::Result<_, _>>()?;
+                match parts[..] {
+                    [major, minor] => Ok(Version(major, minor, 0)),
+                    [major, minor, patch] => Ok(Version(major, minor, patch)),
+                    _ => Err(s.into()),
+                }
+            }
+        }
+
+        impl Serialize for Version {
+            fn serialize<S: ser::Serializer>(&self, s: S) -> std::result::Result<S::Ok, S::Error> {
+                s.serialize_str(&format!("{}.{}.{}", self.0, self.1, self.2))
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Version {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                d: D,
+            ) -> std::result::Result<Self, D::Error> {
+                String::deserialize(d)?
+                    .parse()
+                    .map_err(serde::de::Error::custom)
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            ver: Version,
+        }
+
+        let a = A {
+            ver: Version(0, 1, 0),
+        };
+        let loader = || Loader::new("fromstr").coerce("ver", Coercion::RawString);
+
+        let v = vars!(
+            "FROMSTR_VER" => "1.0.0";
+        );
+        assert_eq!(loader().load(&a).unwrap().ver, Version(1, 0, 0));
+        drop(v);
+
+        // `1.0` is a float in YAML, so it is a string only with the coercion.
+        let _v = vars!(
+            "FROMSTR_VER" => "1.0";
+        );
+        assert!(matches!(load("fromstr", &a), Err(Error::Deserialize(_))));
+        assert_eq!(loader().load(&a).unwrap().ver, Version(1, 0, 0));
+    }
+
+    #[test]
+    fn test_envs_prefix_with_separator() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            host: String,
+            app: App,
+            list: Vec<u32>,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct App {
+            host: String,
+        }
+
+        let a = A::default();
+        assert_eq!(
+            env_var_names::<A>("sep_my_app").unwrap(),
+            vec![
+                "SEP_MY_APP_APP",
+                "SEP_MY_APP_APP_HOST",
+                "SEP_MY_APP_HOST",
+                "SEP_MY_APP_LIST"
+            ]
+        );
+
+        let _v = vars!(
+            "SEP_MY_APP_HOST" => "a";
+            "SEP_MY_APP_APP_HOST" => "b";
+            "SEP_MY_APP_LIST_APPEND" => "1";
+        );
+        let (loaded, warnings) = load_collect_warnings("sep_my_app", &a).unwrap();
+        assert_eq!(
+            loaded,
+            A {
+                host: "a".into(),
+                app: App { host: "b".into() },
+                list: vec![1],
+            }
+        );
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn test_envs_keyword_fields() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            #[serde(rename = "type")]
+            ty: String,
+            r#match: String,
+            r#async: bool,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "KEYWORD_TYPE" => "postgres";
+            "KEYWORD_MATCH" => "exact";
+            "KEYWORD_ASYNC" => "true";
+        );
+        assert_eq!(
+            load("keyword", &a).unwrap(),
+            A {
+                ty: "postgres".into(),
+                r#match: "exact".into(),
+                r#async: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_error_stages() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: Vec<u32>,
+            b: bool,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "STAGES_A" => "[1, 2";
+        );
+        match load("stages", &a) {
+            Err(Error::ValueParse(path, _)) => assert_eq!(path, "STAGES_A"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        let _v = vars!(
+            "STAGES_A" => "[1, 2]";
+            "STAGES_B" => "[1, 2]";
+        );
+        match load("stages", &a) {
+            Err(Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_envs_tuple_elements() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            t3: (u32, bool, String),
+            point: Point,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Point(i32, i32);
+
+        let a = A {
+            t3: (1, false, "a".into()),
+            point: Point(0, 0),
+        };
+
+        let v = vars!(
+            "TUPLEIDX_T3_0" => "8";
+            "TUPLEIDX_T3_1" => "true";
+            "TUPLEIDX_T3_2" => "'6'";
+            "TUPLEIDX_POINT_1" => "-1";
+        );
+        assert_eq!(
+            load("tupleidx", &a).unwrap(),
+            A {
+                t3: (8, true, "6".into()),
+                point: Point(0, -1),
+            }
+        );
+        drop(v);
+
+        // The whole tuple is applied first, then the elements.
+        let v = vars!(
+            "TUPLEIDX_T3" => "[2, true, b]";
+            "TUPLEIDX_T3_2" => "c";
+        );
+        assert_eq!(load("tupleidx", &a).unwrap().t3, (2, true, "c".into()));
+        drop(v);
+
+        let _v = vars!(
+            "TUPLEIDX_T3_3" => "x";
+        );
+        assert_eq!(
+            load("tupleidx", &a),
+            Err(Error::UnpackError(
+                "TUPLEIDX_T3_3".into(),
+                "index out of range for 3 elements".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_load_from_dotenv_files() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: String,
+            b: String,
+            c: String,
+            d: String,
+        }
+
+        let dir = std::env::temp_dir().join(format!("eload-dotenv-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            dir.join(".env"),
+            dir.join(".env.local"),
+            dir.join(".env.production"),
+        ];
+        std::fs::write(&files[0], "DOTENVS_A=env\nDOTENVS_B=env\nDOTENVS_C=env\n").unwrap();
+        std::fs::write(&files[1], "DOTENVS_B=local\nDOTENVS_C=local\n").unwrap();
+        std::fs::write(&files[2], "DOTENVS_C=production\nDOTENVS_D=production\n").unwrap();
+
+        let v = vars!(
+            "DOTENVS_D" => "process";
+        );
+        assert_eq!(
+            load_from_dotenv_files("dotenvs", &A::default(), &files, true).unwrap(),
+            A {
+                a: "env".into(),
+                b: "local".into(),
+                c: "production".into(),
+                d: "process".into(),
+            }
+        );
+        drop(v);
+
+        std::fs::remove_file(&files[1]).unwrap();
+        let a = load_from_dotenv_files("dotenvs", &A::default(), &files, false).unwrap();
+        assert_eq!((a.b.as_str(), a.c.as_str()), ("env", "production"));
+        match load_from_dotenv_files("dotenvs", &A::default(), &files, true) {
+            Err(Error::IoError(msg)) => {
+                assert!(msg.contains(&files[1].display().to_string()), "{}", msg)
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_app() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            app_port: u16,
+        }
+
+        assert_eq!(app_prefix("my-app"), "MY_APP");
+        assert_eq!(app_prefix("eload"), "ELOAD");
+        assert_eq!(app_prefix("web_server-2"), "WEB_SERVER_2");
+
+        let _v = vars!(
+            "ELOAD_APP_PORT" => "8080";
+        );
+        assert_eq!(load_app!(&A::default()).unwrap().app_port, 8080);
+    }
+
+    #[test]
+    fn test_load_sub() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+            host: String,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct App {
+            name: String,
+            http: Http,
+        }
+
+        let mut app = App {
+            name: "app".into(),
+            http: Http {
+                port: 80,
+                host: "localhost".into(),
+            },
+        };
+
+        let v = vars!(
+            "SUBHTTP_PORT" => "8080";
+            "SUBAPP_NAME" => "other";
+            "SUBAPP_HTTP_HOST" => "other";
+        );
+        load_sub("subhttp", &mut app.http).unwrap();
+        assert_eq!(
+            app,
+            App {
+                name: "app".into(),
+                http: Http {
+                    port: 8080,
+                    host: "localhost".into(),
+                },
+            }
+        );
+        drop(v);
+
+        let _v = vars!(
+            "SUBHTTP_PORT" => "x";
+        );
+        assert!(load_sub("subhttp", &mut app.http).is_err());
+        assert_eq!(app.http.port, 8080);
+    }
+
+    #[test]
+    fn test_serialize_errors() {
+        use std::cell::Cell;
+
+        // Fails after serializing successfully `ok` times.
+        #[derive(Deserialize, Debug)]
+        struct Flaky {
+            #[serde(skip)]
+            ok: Cell<usize>,
+        }
+
+        impl Serialize for Flaky {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                s: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                match self.ok.get() {
+                    0 => Err(serde::ser::Error::custom("flaky")),
+                    n => {
+                        self.ok.set(n - 1);
+                        s.serialize_u32(0)
+                    }
+                }
+            }
+        }
+
+        #[derive(Serialize, Deserialize, Debug)]
+        struct A {
+            port: u16,
+            flaky: Flaky,
+        }
+
+        let a = |ok| A {
+            port: 80,
+            flaky: Flaky { ok: Cell::new(ok) },
+        };
+        assert_eq!(
+            load("serr", &a(0)).unwrap_err(),
+            Error::Serialize("flaky".into())
+        );
+        assert_eq!(
+            load("serr", &a(1)).unwrap_err(),
+            Error::PackError("SERR_FLAKY: flaky".into())
+        );
+    }
+
+    #[test]
+    fn test_envs_bytes() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            key: [u8; 4],
+            salt: Vec<u8>,
+            ports: Vec<u16>,
+        }
+
+        let a = A {
+            key: [0; 4],
+            salt: vec![0],
+            ports: vec![80],
+        };
+
+        let v = vars!(
+            "BYTES_KEY" => "deadbeef";
+            "BYTES_SALT" => "0x0102";
+        );
+        assert_eq!(
+            load("bytes", &a).unwrap(),
+            A {
+                key: [0xde, 0xad, 0xbe, 0xef],
+                salt: vec![1, 2],
+                ports: vec![80],
+            }
+        );
+        drop(v);
+
+        // Digits only, which are YAML integers, are still hex.
+        let v = vars!(
+            "BYTES_KEY" => "12345678";
+            "BYTES_SALT" => "[3, 4]";
+        );
+        let b = load("bytes", &a).unwrap();
+        assert_eq!(b.key, [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(b.salt, vec![3, 4]);
+        drop(v);
+
+        let v = vars!(
+            "BYTES_KEY" => "dead";
+        );
+        assert_eq!(
+            load("bytes", &a),
+            Err(Error::UnpackError(
+                "BYTES_KEY".into(),
+                "expected 4 bytes, found 2".into()
+            ))
+        );
+        drop(v);
+
+        let v = vars!(
+            "BYTES_SALT" => "xyz";
+        );
+        assert_eq!(
+            load("bytes", &a),
+            Err(Error::UnpackError("BYTES_SALT".into(), "xyz".into()))
+        );
+        drop(v);
+
+        // Sequences of other integers are not bytes.
+        let _v = vars!(
+            "BYTES_PORTS" => "0102";
+        );
+        assert!(matches!(load("bytes", &a), Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    fn test_envs_tuple_arity() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            t3: (u32, bool, String),
+            a2: [u32; 2],
+        }
+
+        let a = A::default();
+
+        for format in &[Format::Yaml, Format::Json] {
+            let loader = Loader::new("arity").format(*format);
+            let _v = vars!(
+                "ARITY_T3" => "[8, true, \"6\"]";
+                "ARITY_A2" => "[1, 2]";
+            );
+            assert_eq!(
+                loader.load(&a).unwrap(),
+                A {
+                    t3: (8, true, "6".into()),
+                    a2: [1, 2],
+                }
+            );
+
+            let v = vars!(
+                "ARITY_T3" => "[8, true]";
+            );
+            match loader.load(&a) {
+                Err(Error::UnpackError(path, msg)) => {
+                    assert_eq!(path, "ARITY_T3");
+                    assert_eq!(msg, "expected 3 elements, found 2");
+                }
+                r => panic!("unexpected result: {:?}", r),
+            }
+            drop(v);
+
+            let _v = vars!(
+                "ARITY_T3" => "[8, true, \"6\"]";
+                "ARITY_A2" => "[1, 2, 3]";
+            );
+            match loader.load(&a) {
+                Err(Error::UnpackError(path, msg)) => {
+                    assert_eq!(path, "ARITY_A2");
+                    assert_eq!(msg, "expected 2 elements, found 3");
+                }
+                r => panic!("unexpected result: {:?}", r),
+            }
+        }
+    }
+
+    #[test]
+    fn test_envs_seq_elements() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            list: Vec<String>,
+            hosts: Vec<Host>,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Host {
+            name: String,
+            port: u16,
+        }
+
+        let a = A {
+            list: vec!["a".into(), "b".into(), "c".into()],
+            hosts: vec![Host {
+                name: "a".into(),
+                port: 80,
+            }],
+        };
+
+        let _v = vars!(
+            "SEQELEM_LIST_0" => "x";
+            "SEQELEM_LIST_2" => "z";
+            "SEQELEM_LIST_LEN" => "2";
+            "SEQELEM_HOSTS_0_PORT" => "8080";
+        );
+        assert_eq!(
+            load("seqelem", &a).unwrap(),
+            A {
+                list: vec!["x".into(), "b".into()],
+                hosts: vec![Host {
+                    name: "a".into(),
+                    port: 8080,
+                }],
+            }
+        );
+
+        let _v = vars!(
+            "SEQELEM_LIST_LEN" => "two";
+        );
+        match load("seqelem", &a) {
+            Err(Error::UnpackError(path, _)) => assert_eq!(path, "SEQELEM_LIST_LEN"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_envs_option_combos() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct C {
+            a: u32,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            ov: Option<Vec<String>>,
+            vo: Vec<Option<u32>>,
+            oc: Option<C>,
+        }
+
+        let a = A {
+            ov: Some(vec!["x".into()]),
+            vo: vec![Some(1), None, Some(3)],
+            oc: Some(C { a: 1 }),
+        };
+
+        let v = vars!(
+            "COMBO_OV" => "[a, b]";
+            "COMBO_VO_0" => "";
+            "COMBO_VO_1" => "2";
+            "COMBO_OC_A" => "10";
+        );
+        let (loaded, warnings) = load_collect_warnings("combo", &a).unwrap();
+        assert_eq!(
+            loaded,
+            A {
+                ov: Some(vec!["a".into(), "b".into()]),
+                vo: vec![None, Some(2), Some(3)],
+                oc: Some(C { a: 10 }),
+            }
+        );
+        assert_eq!(warnings, vec![Warning::EmptyCoerced("COMBO_VO_0".into())]);
+        drop(v);
+
+        let v = vars!(
+            "COMBO_OV_0" => "y";
+            "COMBO_OV_APPEND" => "z";
+        );
+        assert_eq!(
+            load("combo", &a).unwrap().ov,
+            Some(vec!["y".into(), "z".into()])
+        );
+        drop(v);
+
+        let _v = vars!(
+            "COMBO_OV" => "";
+            "COMBO_VO" => "[~, 5]";
+            "COMBO_OC" => "";
+        );
+        assert_eq!(
+            load("combo", &a).unwrap(),
+            A {
+                ov: None,
+                vo: vec![None, Some(5)],
+                oc: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_nested_options() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            oo: Option<Option<u32>>,
+            ooc: Option<Option<Vec<u32>>>,
+        }
+
+        let bases = [
+            A {
+                oo: None,
+                ooc: None,
+            },
+            A {
+                oo: Some(Some(1)),
+                ooc: Some(Some(vec![1, 2])),
+            },
+        ];
+        for a in &bases {
+            let v = vars!(
+                "NESTOPT_OO" => "42";
+                "NESTOPT_OOC" => "[3]";
+            );
+            let (loaded, warnings) = load_collect_warnings("nestopt", a).unwrap();
+            assert_eq!(
+                loaded,
+                A {
+                    oo: Some(Some(42)),
+                    ooc: Some(Some(vec![3])),
+                }
+            );
+            assert_eq!(warnings, vec![]);
+            drop(v);
+
+            let v = vars!(
+                "NESTOPT_OO" => "";
+                "NESTOPT_OOC" => "~";
+            );
+            assert_eq!(load("nestopt", a).unwrap(), A::default());
+            drop(v);
+        }
+
+        // Elements of an inner option which is `Some` are addressed through both options.
+        let v = vars!(
+            "NESTOPT_OOC_1" => "5";
+        );
+        assert_eq!(
+            load("nestopt", &bases[1]).unwrap().ooc,
+            Some(Some(vec![1, 5]))
+        );
+        drop(v);
+
+        // `Some(None)` and `None` are the same null.
+        let a = A {
+            oo: Some(None),
+            ooc: Some(None),
+        };
+        assert_eq!(load("nestopt", &a).unwrap(), A::default());
+    }
+
+    #[test]
+    fn test_envs_map_entries() {
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            hm: BTreeMap<String, String>,
+            limits: BTreeMap<String, u32>,
+        }
+
+        let loader = Loader::new("entries").map_entry_delimiter(";");
+
+        let v = vars!(
+            "ENTRIES_HM" => "a=b;c=d";
+            "ENTRIES_LIMITS" => "{cpu: 2}";
+        );
+        let a = loader.load(&A::default()).unwrap();
+        assert_eq!(
+            a.hm,
+            vec![("a".into(), "b".into()), ("c".into(), "d".into())]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(a.limits, vec![("cpu".into(), 2)].into_iter().collect());
+        drop(v);
+
+        let _v = vars!(
+            "ENTRIES_HM" => "a=b;c";
+        );
+        match loader.load(&A::default()) {
+            Err(Error::UnpackError(path, entry)) => {
+                assert_eq!(path, "ENTRIES_HM");
+                assert_eq!(entry, "c");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_load_dynamic_prefix() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+        }
+
+        let _v = vars!(
+            "DYNPFX_DEFAULT_A" => "1";
+            "DYNPFX_TENANT_A" => "2";
+        );
+        assert_eq!(
+            load_dynamic_prefix("DYNPFX_INSTANCE", "dynpfx_default", &A::default()).unwrap(),
+            A { a: 1 }
+        );
+
+        let _w = vars!(
+            "DYNPFX_INSTANCE" => "dynpfx_tenant";
+        );
+        assert_eq!(
+            load_dynamic_prefix("DYNPFX_INSTANCE", "dynpfx_default", &A::default()).unwrap(),
+            A { a: 2 }
+        );
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        #[derive(Default)]
+        struct Fields(Vec<String>);
+
+        impl Visit for Fields {
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.push(format!("{}={}", field.name(), value));
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        #[derive(Clone, Default)]
+        struct Capture {
+            spans: Arc<Mutex<Vec<String>>>,
+            events: Arc<Mutex<Vec<String>>>,
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes<'_>) -> Id {
+                let mut fields = Fields::default();
+                span.record(&mut fields);
+                let mut spans = self.spans.lock().unwrap();
+                spans.push(fields.0.join(" "));
+                Id::from_u64(spans.len() as u64)
+            }
+
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.events.lock().unwrap().push(fields.0.join(" "));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            http: Http,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+            host: String,
+        }
+
+        let source = source(&[("TRACE_HTTP_PORT", "80")]);
+        let capture = Capture::default();
+        tracing::subscriber::with_default(capture.clone(), || {
+            load_from("trace", &A::default(), source).unwrap();
+        });
+
+        assert_eq!(
+            *capture.spans.lock().unwrap(),
+            vec!["path=http", "path=http.port", "path=http.host"]
+        );
+        assert_eq!(
+            *capture.events.lock().unwrap(),
+            vec![
+                "name=TRACE_HTTP result=absent",
+                "name=TRACE_HTTP_PORT result=applied",
+                "name=TRACE_HTTP_HOST result=absent",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_envs_seq_element_errors() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            list: Vec<u32>,
+            names: Vec<String>,
+        }
+
+        let a = A {
+            list: vec![0],
+            names: vec!["a".into()],
+        };
+
+        let v = vars!(
+            "ELEMERR_LIST" => "[1, two, 3]";
+        );
+        assert_eq!(
+            load("elemerr", &a),
+            Err(Error::UnpackError("ELEMERR_LIST[1]".into(), "two".into()))
+        );
+        drop(v);
+
+        let _v = vars!(
+            "ELEMERR_LIST" => "[1, 2]";
+            "ELEMERR_NAMES" => "[b, c]";
+        );
+        assert_eq!(
+            load("elemerr", &a).unwrap(),
+            A {
+                list: vec![1, 2],
+                names: vec!["b".into(), "c".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_envs_seq_append() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            list: Vec<String>,
+        }
+
+        let a = A {
+            list: vec!["a".into(), "b".into()],
+        };
+
+        let v = vars!(
+            "APPEND_LIST_0" => "z";
+            "APPEND_LIST_APPEND" => "c";
+        );
+        assert_eq!(
+            load("append", &a).unwrap().list,
+            vec!["z".to_string(), "b".into(), "c".into()]
+        );
+        drop(v);
+
+        let v = vars!(
+            "APPEND_LIST_PUSH" => "c";
+            "APPEND_LIST_APPEND" => "d";
+        );
+        let loader = Loader::new("append").append_suffix("push");
+        assert_eq!(
+            loader.load(&a).unwrap().list,
+            vec!["a".to_string(), "b".into(), "c".into()]
+        );
+        drop(v);
+
+        // The whole sequence, then the elements, then the appended element, then the length.
+        let v = vars!(
+            "APPEND_LIST" => "[p, q, r]";
+            "APPEND_LIST_0" => "z";
+            "APPEND_LIST_APPEND" => "s";
+            "APPEND_LIST_LEN" => "4";
+        );
+        assert_eq!(
+            load("append", &a).unwrap().list,
+            vec!["z".to_string(), "q".into(), "r".into(), "s".into()]
+        );
+        drop(v);
+
+        let _v = vars!(
+            "APPEND_LIST" => "[p, q, r]";
+            "APPEND_LIST_0" => "z";
+            "APPEND_LIST_APPEND" => "s";
+            "APPEND_LIST_LEN" => "3";
+        );
+        assert_eq!(
+            load("append", &a).unwrap().list,
+            vec!["z".to_string(), "q".into(), "r".into()]
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+            computed: u32,
+            b: B,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+            runtime: u32,
+        }
+
+        let a = A::default();
+
+        let _v = vars!(
+            "SKIP_A" => "1";
+            "SKIP_COMPUTED" => "1";
+            "SKIP_B_A" => "1";
+            "SKIP_B_RUNTIME" => "1";
+        );
+        let loader = Loader::new("skip").skip(&["computed", "b.runtime"]);
+        let (a, warnings) = loader.load_collect_warnings(&a).unwrap();
+        assert_eq!(
+            a,
+            A {
+                a: 1,
+                computed: 0,
+                b: B { a: 1, runtime: 0 },
+            }
+        );
+        assert!(warnings.is_empty());
+
+        let names: Vec<_> = loader
+            .describe(&a)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(names, vec!["SKIP_A", "SKIP_B", "SKIP_B_A"]);
+    }
 }
@@ -0,0 +1,2356 @@
+use crate::{
+    find_and_update, lookup, merge, remove_entry, Error, ProcessEnv, RefSyntax, Report, Result,
+    Serializer, VarSource, Warning,
+};
+use log::*;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+
+/// How an environment variable set to an empty string is treated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmptyPolicy {
+    /// The value becomes null, e.g. `None` for `Option` fields.
+    #[default]
+    Null,
+    /// The value becomes null for `Option` fields, and is an error for the other fields.
+    Strict,
+    /// The value becomes an empty string.
+    Literal,
+    /// The variable is ignored as if it were not set.
+    Ignore,
+}
+
+/// How the value of an environment variable is interpreted instead of parsing it as YAML.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Coercion {
+    /// The value is passed to the field as a string without parsing.
+    ///
+    /// Useful for types deserialized from strings which YAML can misinterpret,
+    /// e.g. `chrono::DateTime<Utc>` or `time::OffsetDateTime` from RFC 3339 strings,
+    /// or `semver::Version` from `1.0`, which YAML reads as a float.
+    RawString,
+    /// The value is a path passed to the field as a string, with a leading `~` expanded to
+    /// the home directory, and `$VAR` and `${VAR}` to the variables of the source.
+    ///
+    /// Paths are not expanded unless the field is coerced, as the type of the field isn't known.
+    Path,
+}
+
+/// What to do when an environment variable changes a value set by the document of `load_patch`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The variable takes precedence silently.
+    #[default]
+    Allow,
+    /// The variable takes precedence with `Warning::Conflict`.
+    Warn,
+    /// The load fails with `Error::Conflict`.
+    Error,
+}
+
+/// Format of the values of environment variables.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// YAML, e.g. `[1, 2]` or `{a: 1}`.
+    #[default]
+    Yaml,
+    /// JSON, e.g. `[1, 2]` or `{"a": 1}`. Strings can be given without quotes.
+    Json,
+}
+
+type Transform = Box<dyn Fn(String) -> String + Send + Sync>;
+type Validator = Box<dyn Fn(&Value) -> std::result::Result<(), String> + Send + Sync>;
+type WarningSink = Box<dyn Fn(&Warning) + Send + Sync>;
+type Resolver = Box<dyn Fn(&str, String) -> std::result::Result<String, String> + Send + Sync>;
+type NameFn = Box<dyn Fn(&[String]) -> String + Send + Sync>;
+
+/// Configurable loader of environment variables.
+///
+/// `eload::load(pfx, &t)` is a shorthand of `Loader::new(pfx).load(&t)`.
+///
+/// Options applied to a specific field take the dot-separated path
+/// of the field without the prefix, e.g. `http.port` for `APP_HTTP_PORT`.
+///
+/// # Thread safety
+///
+/// `Loader` is `Send` and `Sync`, and each call of `load` keeps its state to itself,
+/// so loaders can be used from multiple threads concurrently. Loaders only share
+/// what their sources share: `ProcessEnv` reads the process environment, which
+/// can change under concurrent `std::env::set_var` calls. Use a source other than
+/// `ProcessEnv` to fully isolate a loader.
+///
+/// # Reuse
+///
+/// A loader is built once and reused for many values of a type, e.g. the config
+/// of each tenant. Each load walks its own value, so sequence elements, map entries
+/// and the insides of options are those of the value; nothing is kept per type.
+pub struct Loader {
+    pub(crate) prefix: String,
+    pub(crate) source: Box<dyn VarSource + Send + Sync>,
+    sink: WarningSink,
+    name_fn: Option<NameFn>,
+    unicode_uppercase: bool,
+    pub(crate) case_insensitive: bool,
+    pub(crate) empty: EmptyPolicy,
+    pub(crate) format: Format,
+    pub(crate) prefix_aliases: Vec<String>,
+    pub(crate) field_prefixes: HashMap<String, String>,
+    pub(crate) candidates: HashMap<String, Vec<String>>,
+    overlays: HashMap<String, Box<dyn VarSource + Send + Sync>>,
+    overlay_var: Option<String>,
+    pub(crate) ref_syntax: Option<RefSyntax>,
+    pub(crate) transforms: HashMap<String, Transform>,
+    pub(crate) coercions: HashMap<String, Coercion>,
+    pub(crate) bool_tokens: HashMap<String, (Vec<String>, Vec<String>)>,
+    pub(crate) validators: HashMap<String, Validator>,
+    pub(crate) secrets: HashSet<String>,
+    pub(crate) skips: HashSet<String>,
+    pub(crate) scopes: Vec<String>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) max_value_nodes: Option<usize>,
+    pub(crate) append_suffix: String,
+    pub(crate) root_var: bool,
+    pub(crate) trim: bool,
+    pub(crate) resolver: Option<Resolver>,
+    pub(crate) map_entry_delimiter: Option<String>,
+    pub(crate) map_kv_delimiter: String,
+    pub(crate) include_var: Option<String>,
+    pub(crate) dynamic_maps: HashMap<String, Value>,
+    pub(crate) negation_prefix: Option<String>,
+    pub(crate) log_summary: bool,
+    pub(crate) catch_all: Option<String>,
+    pub(crate) allowlist: Option<HashSet<String>>,
+    pub(crate) null_sentinel: Option<String>,
+    pub(crate) delete_sentinel: Option<String>,
+    conflict_policy: ConflictPolicy,
+    pub(crate) int_grouping: Option<char>,
+    pub(crate) base64_values: bool,
+    pub(crate) assignments_var: Option<String>,
+}
+
+impl Loader {
+    /// Create a loader which reads environment variables starting with `pfx`.
+    ///
+    /// The prefix is a single segment of the names, so it may contain separators,
+    /// e.g. `my_app` for `MY_APP_HOST`. Only the names starting with the prefix and
+    /// a separator are under the prefix, e.g. `APP_HOST` but not `APPLE_HOST` for `app`.
+    pub fn new(pfx: &str) -> Self {
+        Self {
+            prefix: pfx.into(),
+            source: Box::new(ProcessEnv),
+            sink: Box::new(|warning| warn!("warning: {}", warning)),
+            name_fn: None,
+            unicode_uppercase: false,
+            case_insensitive: false,
+            empty: EmptyPolicy::default(),
+            format: Format::default(),
+            prefix_aliases: Vec::new(),
+            field_prefixes: HashMap::new(),
+            candidates: HashMap::new(),
+            overlays: HashMap::new(),
+            overlay_var: None,
+            ref_syntax: None,
+            transforms: HashMap::new(),
+            coercions: HashMap::new(),
+            bool_tokens: HashMap::new(),
+            validators: HashMap::new(),
+            secrets: HashSet::new(),
+            skips: HashSet::new(),
+            scopes: Vec::new(),
+            max_depth: None,
+            max_value_nodes: None,
+            append_suffix: "append".into(),
+            root_var: false,
+            trim: false,
+            resolver: None,
+            map_entry_delimiter: None,
+            map_kv_delimiter: "=".into(),
+            include_var: None,
+            dynamic_maps: HashMap::new(),
+            negation_prefix: None,
+            log_summary: false,
+            catch_all: None,
+            allowlist: None,
+            null_sentinel: None,
+            delete_sentinel: None,
+            conflict_policy: ConflictPolicy::default(),
+            int_grouping: None,
+            base64_values: false,
+            assignments_var: None,
+        }
+    }
+
+    /// Set the source of variables. Defaults to `ProcessEnv`.
+    pub fn source<S: VarSource + Send + Sync + 'static>(mut self, source: S) -> Self {
+        self.source = Box::new(source);
+        self
+    }
+
+    /// Set the sources of variables tried in order, e.g. the process environment,
+    /// then a `.env` file, then built-in defaults.
+    pub fn sources(self, sources: Vec<Box<dyn VarSource + Send + Sync>>) -> Self {
+        self.source(sources)
+    }
+
+    /// Set the function receiving the warnings found by `load`. Defaults to logging them.
+    pub fn warning_sink<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Warning) + Send + Sync + 'static,
+    {
+        self.sink = Box::new(f);
+        self
+    }
+
+    /// Set the function naming environment variables.
+    ///
+    /// The function takes the prefix followed by the field names, e.g. `["app", "http", "port"]`,
+    /// and returns the name of the variable. Defaults to joining the uppercased segments
+    /// with `_`, e.g. `APP_HTTP_PORT`.
+    pub fn name_fn<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&[String]) -> String + Send + Sync + 'static,
+    {
+        self.name_fn = Some(Box::new(f));
+        self
+    }
+
+    /// Get the name of the environment variable for the prefix and field names in `segments`.
+    pub(crate) fn var_name(&self, segments: &[String]) -> String {
+        match &self.name_fn {
+            Some(f) => f(segments),
+            None => segments
+                .iter()
+                .map(|s| {
+                    if self.unicode_uppercase {
+                        s.to_uppercase()
+                    } else {
+                        s.to_ascii_uppercase()
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("_"),
+        }
+    }
+
+    /// Get the name of the environment variable for the prefix and field names in `segments`,
+    /// replacing the prefix and the top-level field by the prefix of the field if set.
+    pub(crate) fn path_name(&self, segments: &[String]) -> String {
+        match segments
+            .get(1)
+            .and_then(|field| self.field_prefixes.get(field))
+        {
+            Some(prefix) => {
+                let mut segments = segments[1..].to_vec();
+                segments[0] = prefix.clone();
+                self.var_name(&segments)
+            }
+            None => self.var_name(segments),
+        }
+    }
+
+    /// Uppercase the names of environment variables by the full Unicode rules
+    /// instead of only ASCII letters.
+    ///
+    /// Unicode uppercasing can change the length of names, e.g. `ß` becomes `SS`,
+    /// so by default only ASCII letters are uppercased and the others are kept as is.
+    pub fn unicode_uppercase(mut self, enabled: bool) -> Self {
+        self.unicode_uppercase = enabled;
+        self
+    }
+
+    /// Match the names of variables ignoring ASCII case, e.g. `APP_host` for `APP_HOST`.
+    ///
+    /// If several variables match, the exact match or else the first in sorted order
+    /// is taken, and a `Warning::CaseCollision` is reported.
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// Set how empty environment variables are treated. Defaults to `EmptyPolicy::Null`.
+    pub fn empty_value(mut self, policy: EmptyPolicy) -> Self {
+        self.empty = policy;
+        self
+    }
+
+    /// Set the format of the values. Defaults to `Format::Yaml`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Fall back to the variables with the prefixes `aliases`, in order, when the variable
+    /// with the prefix is not set, e.g. to `OLD_HOST` for `NEW_HOST`.
+    ///
+    /// Use of an alias is reported as a `Warning::DeprecatedPrefix`.
+    pub fn prefix_aliases(mut self, aliases: &[&str]) -> Self {
+        self.prefix_aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+        self
+    }
+
+    /// Load the top-level field `field` from the variables with the prefix `pfx`
+    /// in place of the prefix of the loader, e.g. `SRV_PORT` for `server.port`.
+    pub fn field_prefix(mut self, field: &str, pfx: &str) -> Self {
+        self.field_prefixes.insert(field.into(), pfx.into());
+        self
+    }
+
+    /// Load the field at `path` from the variable `name` as is, e.g. `DATABASE_URL`
+    /// for `database_url`, instead of the name derived from the prefix and the path.
+    pub fn alias_var(self, path: &str, name: &str) -> Self {
+        self.candidates(path, &[name])
+    }
+
+    /// Load the field at `path` from the first set variable in `names`, e.g.
+    /// `&["APP_DATABASE_URL", "DATABASE_URL"]`, instead of the name derived
+    /// from the prefix and the path.
+    pub fn candidates(mut self, path: &str, names: &[&str]) -> Self {
+        self.candidates
+            .insert(path.into(), names.iter().map(|n| n.to_string()).collect());
+        self
+    }
+
+    /// Add the variables of `source` as the overlay of the environment `name`, e.g. `prod`.
+    ///
+    /// The overlay of the environment selected by the overlay variable, `PFX_ENV`
+    /// by default, is used for the variables not set in the source.
+    pub fn overlay<S: VarSource + Send + Sync + 'static>(mut self, name: &str, source: S) -> Self {
+        self.overlays.insert(name.into(), Box::new(source));
+        self
+    }
+
+    /// Set the name of the variable selecting the overlay. Defaults to `PFX_ENV`.
+    pub fn overlay_var(mut self, name: &str) -> Self {
+        self.overlay_var = Some(name.into());
+        self
+    }
+
+    pub(crate) fn overlay_var_name(&self) -> String {
+        match &self.overlay_var {
+            Some(name) => name.clone(),
+            None => self.var_name(&[self.prefix.clone(), "env".into()]),
+        }
+    }
+
+    /// Get the overlay selected by the overlay variable, if any.
+    pub(crate) fn active_overlay(&self) -> Result<Option<&(dyn VarSource + Send + Sync)>> {
+        if self.overlays.is_empty() {
+            return Ok(None);
+        }
+        Ok(self
+            .source
+            .var(&self.overlay_var_name())?
+            .and_then(|name| self.overlays.get(&name))
+            .map(|overlay| overlay.as_ref()))
+    }
+
+    /// Also apply the prefixes listed in the variable `suffix` under the prefix,
+    /// e.g. `include_var("include")` with `PFX_INCLUDE=extra,local` applies
+    /// the `EXTRA_*` and then the `LOCAL_*` variables over the `PFX_*` ones.
+    ///
+    /// The include variables of included prefixes are followed as well.
+    /// A prefix is applied at most once, so include loops are harmless.
+    /// Prefix aliases, overlays, field prefixes and candidates are not used
+    /// for included prefixes.
+    pub fn include_var(mut self, suffix: &str) -> Self {
+        self.include_var = Some(suffix.into());
+        self
+    }
+
+    /// Also apply the assignments listed in the variable `suffix` under the prefix,
+    /// e.g. `assignments_var("set")` with `PFX_SET=http.port=8080;db.hosts[0]=x`,
+    /// after the other variables.
+    ///
+    /// Each assignment is a dotted field path, with `[index]` for sequence elements,
+    /// and a value parsed like the value of a variable. The fields and elements must
+    /// exist in the base value.
+    pub fn assignments_var(mut self, suffix: &str) -> Self {
+        self.assignments_var = Some(suffix.into());
+        self
+    }
+
+    /// Create the entries of the map at `path`, or the root map if `path` is empty,
+    /// found in variable names from `V::default()`, e.g. `PFX_SERVICES_WEB_PORT=80`
+    /// creates the entry `web` with `port` set to 80.
+    ///
+    /// Keys are the lowercased parts of the names between the map and a field of `V`,
+    /// matched with the longest field names first.
+    pub fn dynamic_map<V: Serialize + Default>(mut self, path: &str) -> Self {
+        let template = serde_yaml::to_value(V::default()).unwrap_or_default();
+        self.dynamic_maps.insert(path.into(), template);
+        self
+    }
+
+    /// Also read bool fields from their negations, e.g. `PFX_NO_TLS=1` for `tls = false`
+    /// with `negation_prefix("NO_")`.
+    ///
+    /// Negations are truthy if `1`, `true`, `yes` or `on`, and ignored if `0`, `false`, `no`,
+    /// `off` or empty. If both the variable and its negation are set, the variable
+    /// is used with `Warning::NegationConflict`.
+    pub fn negation_prefix(mut self, prefix: &str) -> Self {
+        self.negation_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Log a summary of each load at the debug level, with the numbers of
+    /// the variables checked and applied, and of the ambiguous and unused variables.
+    pub fn log_summary(mut self, enabled: bool) -> Self {
+        self.log_summary = enabled;
+        self
+    }
+
+    /// Set the fields to null, e.g. `None` for options, by the variables set to `sentinel`,
+    /// e.g. `PFX_NAME=__NULL__` with `null_sentinel("__NULL__")`.
+    ///
+    /// Unlike empty values, which some shells drop, the sentinel is passed around as is.
+    /// The sentinel is compared with the raw value before it is resolved or transformed.
+    pub fn null_sentinel(mut self, sentinel: &str) -> Self {
+        self.null_sentinel = Some(sentinel.into());
+        self
+    }
+
+    /// Remove the grouping separator `sep` from the values of integer fields before parsing,
+    /// e.g. `1,000,000` with `int_grouping(',')`. Values with the separator must be decimal,
+    /// so other separators, as in `1,000_000`, are errors.
+    pub fn int_grouping(mut self, sep: char) -> Self {
+        self.int_grouping = Some(sep);
+        self
+    }
+
+    /// Decode the values prefixed with `b64:` as base64 before parsing them,
+    /// e.g. `PFX_LABELS=b64:YTogMQpiOiAyCg==` for `{a: 1, b: 2}`, so that values with
+    /// newlines or quotes pass through environments which only allow simple strings.
+    pub fn base64_values(mut self, enabled: bool) -> Self {
+        self.base64_values = enabled;
+        self
+    }
+
+    /// Set what to do when a variable changes a value set by the document of `load_patch`.
+    /// Defaults to `ConflictPolicy::Allow`.
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Remove the entries of maps whose variables are set to `sentinel`,
+    /// e.g. `PFX_LIMITS_CPU=__DELETE__` with `delete_sentinel("__DELETE__")`
+    /// removes the entry `cpu` of `limits`.
+    ///
+    /// Removed entries are in reports as `Applied` with `Operation::Delete`.
+    pub fn delete_sentinel(mut self, sentinel: &str) -> Self {
+        self.delete_sentinel = Some(sentinel.into());
+        self
+    }
+
+    /// Only read the variables named in `names`, e.g. `&["PFX_HOST", "PFX_PORT"]`,
+    /// ignoring the others as if they were not set.
+    ///
+    /// The fields of the other variables are not probed, so they are not in reports either.
+    pub fn allowlist(mut self, names: &[&str]) -> Self {
+        self.allowlist = Some(names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Whether the variable `name` may be read.
+    pub(crate) fn is_allowed(&self, name: &str) -> bool {
+        self.allowlist
+            .as_ref()
+            .is_none_or(|names| names.contains(name))
+    }
+
+    /// Insert the variables under the prefix which match no field into the map at `path`,
+    /// keyed by the names without the prefix, e.g. `PFX_CUSTOM_THING=1` as `CUSTOM_THING: "1"`.
+    ///
+    /// The values are strings, so the map should be a map of strings such as `HashMap<String, String>`.
+    pub fn catch_all(mut self, path: &str) -> Self {
+        self.catch_all = Some(path.into());
+        self
+    }
+
+    /// Expand references to other variables of the source in values, written in `syntax`.
+    /// References to unset variables expand to empty strings.
+    pub fn expand_refs(mut self, syntax: RefSyntax) -> Self {
+        self.ref_syntax = Some(syntax);
+        self
+    }
+
+    /// Resolve the raw values by `f`, e.g. fetching secrets referred to by the values.
+    ///
+    /// `f` takes the name and the raw value of a variable, and is called only for
+    /// the variables which are set, so fields without variables cost nothing.
+    /// Errors of `f` are returned as `Error::UnpackError` of the variable.
+    pub fn resolver<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&str, String) -> std::result::Result<String, String> + Send + Sync + 'static,
+    {
+        self.resolver = Some(Box::new(f));
+        self
+    }
+
+    /// Trim leading and trailing whitespace from the raw values. Defaults to off.
+    ///
+    /// Fields with a `transform` get their raw values untrimmed, so that
+    /// whitespace can be kept where significant.
+    pub fn trim(mut self, enabled: bool) -> Self {
+        self.trim = enabled;
+        self
+    }
+
+    /// Transform the raw value of the environment variable for the field at `path`
+    /// before it is parsed.
+    pub fn transform<F>(mut self, path: &str, f: F) -> Self
+    where
+        F: Fn(String) -> String + Send + Sync + 'static,
+    {
+        self.transforms.insert(path.into(), Box::new(f));
+        self
+    }
+
+    /// Validate the parsed value of the environment variable for the field at `path` by `f`
+    /// before it is applied. Errors of `f` are returned as `Error::UnpackError` of the variable.
+    pub fn validate_field<F>(mut self, path: &str, f: F) -> Self
+    where
+        F: Fn(&Value) -> std::result::Result<(), String> + Send + Sync + 'static,
+    {
+        self.validators.insert(path.into(), Box::new(f));
+        self
+    }
+
+    /// Interpret the value of the environment variable for the field at `path` with `coercion`.
+    pub fn coerce(mut self, path: &str, coercion: Coercion) -> Self {
+        self.coercions.insert(path.into(), coercion);
+        self
+    }
+
+    /// Also accept the words `truthy` for `true` and `falsy` for `false` for the bool field
+    /// at `path`, e.g. `bool_tokens("tls", &["enabled"], &["disabled"])`.
+    /// The words are matched ignoring ASCII case. Other bool fields are unaffected.
+    pub fn bool_tokens(mut self, path: &str, truthy: &[&str], falsy: &[&str]) -> Self {
+        let words = |words: &[&str]| words.iter().map(|w| w.to_string()).collect();
+        self.bool_tokens
+            .insert(path.into(), (words(truthy), words(falsy)));
+        self
+    }
+
+    /// Mark the field at `path` as secret so that its value is redacted in outputs
+    /// such as `print_env_vars`.
+    pub fn secret(mut self, path: &str) -> Self {
+        self.secrets.insert(path.into());
+        self
+    }
+
+    /// Never load the fields at `paths` or the fields nested in them,
+    /// e.g. computed or runtime-only fields.
+    pub fn skip(mut self, paths: &[&str]) -> Self {
+        self.skips.extend(paths.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Restrict loading to the subtrees at `paths`, e.g. `&["http"]`.
+    ///
+    /// Fields outside the subtrees are neither loaded nor described, and only
+    /// the variables inside the subtrees are reported as unused.
+    pub fn scope(mut self, paths: &[&str]) -> Self {
+        self.scopes.extend(paths.iter().map(|p| p.to_string()));
+        self
+    }
+
+    /// Load the whole value from the variable named by the prefix alone, e.g. `APP`,
+    /// as a YAML mapping merged into the value before the other variables are applied.
+    ///
+    /// Disabled by default, as the name can collide with unrelated variables such as `PATH`.
+    pub fn root_var(mut self, enabled: bool) -> Self {
+        self.root_var = enabled;
+        self
+    }
+
+    /// Accept maps as entries delimited by `delim`, e.g. `a=b;c=d` for `;`.
+    ///
+    /// Values of maps starting with `{` are still parsed as YAML mappings.
+    pub fn map_entry_delimiter(mut self, delim: &str) -> Self {
+        self.map_entry_delimiter = Some(delim.into());
+        self
+    }
+
+    /// Set the delimiter between the keys and values of map entries. Defaults to `=`.
+    pub fn map_kv_delimiter(mut self, delim: &str) -> Self {
+        self.map_kv_delimiter = delim.into();
+        self
+    }
+
+    /// Set the suffix of the variables appending an element to sequences,
+    /// e.g. `APP_LIST_APPEND`. Defaults to `append`.
+    pub fn append_suffix(mut self, suffix: &str) -> Self {
+        self.append_suffix = suffix.into();
+        self
+    }
+
+    /// Fail with `Error::DepthExceeded` on fields nested deeper than `depth`.
+    /// The fields of the root struct are at depth 1. Defaults to no limit.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Fail with `Error::ValueTooLarge` when a variable parses into more than `nodes` nodes,
+    /// counting each element, key and value. Defaults to no limit.
+    pub fn max_value_nodes(mut self, nodes: usize) -> Self {
+        self.max_value_nodes = Some(nodes);
+        self
+    }
+
+    /// Load environment variables into the struct members of `t`.
+    ///
+    /// Warnings found while loading are passed to the warning sink.
+    ///
+    /// The loaded value is always deserialized anew, even if no variable is set,
+    /// as `t` is borrowed. Use `load_cloned` to clone `t` instead in that case.
+    pub fn load<T: Serialize + DeserializeOwned>(&self, t: &T) -> Result<T> {
+        let (t, warnings) = self.load_collect_warnings(t)?;
+        self.warn(&warnings);
+        Ok(t)
+    }
+
+    /// Same as `load` but returns a clone of `t` if no environment variable changes it,
+    /// without the round trip through the intermediate value.
+    pub fn load_cloned<T: Serialize + DeserializeOwned + Clone>(&self, t: &T) -> Result<T> {
+        let ser = self.walk(t)?;
+        let modified = ser.modified;
+        let (value, report) = ser.finish();
+        let t = if modified {
+            serde_yaml::from_value(value).map_err(Error::deserialize)?
+        } else {
+            t.clone()
+        };
+        self.warn(&report.warnings);
+        Ok(t)
+    }
+
+    /// Same as `load` but loads `sub` in place, e.g. a part of a larger config loaded
+    /// under its own prefix: `Loader::new("http").load_sub(&mut app.http)`.
+    ///
+    /// `sub` is the root, so its fields are `HTTP_PORT` rather than `APP_HTTP_PORT`.
+    /// It is left as is on errors.
+    pub fn load_sub<T: Serialize + DeserializeOwned>(&self, sub: &mut T) -> Result<()> {
+        *sub = self.load(sub)?;
+        Ok(())
+    }
+
+    /// Same as `load` but merges the YAML or JSON document read from `reader` into `t`
+    /// before loading environment variables. Environment variables take precedence over the document,
+    /// unless disallowed by `Loader::conflict_policy`.
+    pub fn load_patch<T, R>(&self, t: &T, mut reader: R) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        R: std::io::Read,
+    {
+        let mut patch = String::new();
+        reader
+            .read_to_string(&mut patch)
+            .map_err(|e| Error::IoError(e.to_string()))?;
+        let mut value = serde_yaml::to_value(t).map_err(Error::serialize)?;
+        let patch: Value = serde_yaml::from_str(&patch).map_err(Error::deserialize)?;
+        merge(&mut value, patch.clone());
+        let base = serde_yaml::from_value::<T>(value).map_err(Error::deserialize)?;
+
+        let (value, mut report) = self.walk(&base)?.finish();
+        if self.conflict_policy != ConflictPolicy::Allow {
+            for applied in &report.applied {
+                let set = match lookup(&patch, &applied.path) {
+                    Some(set) => set,
+                    None => continue,
+                };
+                if lookup(&value, &applied.path) == Some(set) {
+                    continue;
+                }
+                match self.conflict_policy {
+                    ConflictPolicy::Error => return Err(Error::Conflict(applied.name.clone())),
+                    _ => report
+                        .warnings
+                        .push(Warning::Conflict(applied.name.clone())),
+                }
+            }
+        }
+        self.warn(&report.warnings);
+        serde_yaml::from_value(value).map_err(Error::deserialize)
+    }
+
+    /// Same as `load_patch` but with the YAML or JSON file named by the variable `file_var`,
+    /// e.g. `CONFIG_FILE=/etc/app.yaml`. Same as `load` if `file_var` is not set.
+    pub fn load_with_file_var<T>(&self, file_var: &str, t: &T) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        match self.source.var(file_var)? {
+            Some(file) => {
+                let reader = std::fs::File::open(&file)
+                    .map_err(|e| Error::IoError(format!("{} ({}): {}", file_var, file, e)))?;
+                self.load_patch(t, reader)
+            }
+            None => self.load(t),
+        }
+    }
+
+    /// Same as `load` but returns the warnings instead of passing them to the sink.
+    pub fn load_collect_warnings<T: Serialize + DeserializeOwned>(
+        &self,
+        t: &T,
+    ) -> Result<(T, Vec<Warning>)> {
+        let (value, report) = self.walk(t)?.finish();
+        let t = serde_yaml::from_value(value).map_err(Error::deserialize)?;
+        Ok((t, report.warnings))
+    }
+
+    /// Same as `load` but takes `t` by value and also returns the report of the load.
+    ///
+    /// If no environment variable changes the value, `t` is returned as is
+    /// without the round trip through the intermediate value.
+    /// Warnings are in the report instead of being passed to the sink.
+    pub fn load_with_report<T: Serialize + DeserializeOwned>(&self, t: T) -> Result<(T, Report)> {
+        let ser = self.walk(&t)?;
+        let modified = ser.modified;
+        let (value, report) = ser.finish();
+        if !modified {
+            return Ok((t, report));
+        }
+        let t = serde_yaml::from_value(value).map_err(Error::deserialize)?;
+        Ok((t, report))
+    }
+
+    /// Same as `load` but skips the environment variables which fail to apply,
+    /// returning their errors along with the partially loaded value.
+    ///
+    /// Errors which are not specific to a variable, such as `DepthExceeded`,
+    /// leave `t` as is.
+    pub fn load_lenient<T>(&self, t: &T) -> (T, Vec<Error>)
+    where
+        T: Serialize + DeserializeOwned + Clone,
+    {
+        let base = match serde_yaml::to_value(t) {
+            Ok(base) => base,
+            Err(e) => return (t.clone(), vec![Error::serialize(e)]),
+        };
+        let mut ser = Serializer::new(self, &self.prefix, base.clone());
+        ser.errors = Some(Vec::new());
+        if let Err(e) = t
+            .serialize(&mut ser)
+            .map_err(|e| ser.pack_context(e))
+            .and_then(|_| ser.include(t))
+            .and_then(|_| ser.assign())
+            .and_then(|_| ser.catch_all())
+        {
+            let mut errors = ser.errors.take().unwrap_or_default();
+            errors.push(e);
+            return (t.clone(), errors);
+        }
+        let mut errors = ser.errors.take().unwrap_or_default();
+        let (value, report) = ser.finish();
+        self.warn(&report.warnings);
+        if let Ok(t) = serde_yaml::from_value(value) {
+            return (t, errors);
+        }
+
+        // Some values don't fit their fields; apply them one by one to find out which.
+        let (mut value, mut t) = (base, t.clone());
+        for applied in report.applied {
+            let mut next = value.clone();
+            match applied.result() {
+                None => remove_entry(&mut next, &applied.path),
+                Some(value) if !find_and_update(&mut next, &applied.path, value) => continue,
+                Some(_) => {}
+            }
+            match serde_yaml::from_value(next.clone()) {
+                Ok(next_t) => {
+                    value = next;
+                    t = next_t;
+                }
+                Err(_)
+                    if errors.iter().any(
+                        |e| matches!(e, Error::UnpackError(name, _) if *name == applied.name),
+                    ) => {}
+                Err(e) => errors.push(Error::UnpackError(applied.name, e.to_string())),
+            }
+        }
+        (t, errors)
+    }
+
+    /// Get the value of the single field at `path`, e.g. `&["http", "port"]` for `PFX_HTTP_PORT`.
+    ///
+    /// Returns `None` if the environment variable is not set.
+    pub fn get_field<V: DeserializeOwned>(&self, path: &[&str]) -> Result<Option<V>> {
+        let mut ser = Serializer::new(self, &self.prefix, Value::Null);
+        for name in path {
+            ser.enter(name)?;
+        }
+        let value = ser.read(&ser.path(), None)?;
+        self.warn(&ser.warnings);
+        match value {
+            Some(value) => Ok(Some(
+                serde_yaml::from_value(value).map_err(Error::deserialize)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Pass `warnings` to the warning sink.
+    pub(crate) fn warn(&self, warnings: &[Warning]) {
+        for warning in warnings {
+            (self.sink)(warning);
+        }
+    }
+
+    /// Walk the fields of `t` applying environment variables to its value.
+    pub(crate) fn walk<T: Serialize>(&self, t: &T) -> Result<Serializer<'_>> {
+        let value = serde_yaml::to_value(t).map_err(Error::serialize)?;
+        let mut ser = Serializer::new(self, &self.prefix, value);
+        t.serialize(&mut ser).map_err(|e| ser.pack_context(e))?;
+        ser.include(t)?;
+        ser.assign()?;
+        ser.catch_all()?;
+        Ok(ser)
+    }
+
+    /// Walk the fields of `t` only to collect the probes, reading no variables.
+    pub(crate) fn walk_probes<T: Serialize>(&self, t: &T) -> Result<Serializer<'_>> {
+        let value = serde_yaml::to_value(t).map_err(Error::serialize)?;
+        let mut ser = Serializer::new(self, &self.prefix, value);
+        ser.probe_only = true;
+        t.serialize(&mut ser).map_err(|e| ser.pack_context(e))?;
+        ser.include(t)?;
+        Ok(ser)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{source, vars};
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    struct A {
+        o: Option<String>,
+        s: String,
+    }
+
+    fn a() -> A {
+        A {
+            o: Some("o".into()),
+            s: "s".into(),
+        }
+    }
+
+    #[test]
+    fn test_empty_null() {
+        let _v = vars!(
+            "EMPTYNULL_O" => "";
+        );
+        let loader = Loader::new("emptynull").empty_value(EmptyPolicy::Null);
+        assert_eq!(
+            loader.load(&a()).unwrap(),
+            A {
+                o: None,
+                s: "s".into()
+            }
+        );
+
+        let _v = vars!(
+            "EMPTYNULL_S" => "";
+        );
+        match loader.load(&a()) {
+            Err(crate::Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_empty_strict() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            port: u16,
+            backup: Option<u16>,
+        }
+
+        let b = B {
+            port: 80,
+            backup: Some(8080),
+        };
+
+        let _v = vars!(
+            "EMPTYSTRICT_BACKUP" => "";
+        );
+        let loader = Loader::new("emptystrict").empty_value(EmptyPolicy::Strict);
+        assert_eq!(
+            loader.load(&b).unwrap(),
+            B {
+                port: 80,
+                backup: None
+            }
+        );
+
+        let _v = vars!(
+            "EMPTYSTRICT_PORT" => "";
+        );
+        match loader.load(&b) {
+            Err(crate::Error::UnpackError(path, msg)) => {
+                assert_eq!(path, "EMPTYSTRICT_PORT");
+                assert_eq!(msg, "empty value not allowed for non-optional field");
+            }
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_empty_literal() {
+        let _v = vars!(
+            "EMPTYLIT_O" => "";
+            "EMPTYLIT_S" => "";
+        );
+        let loader = Loader::new("emptylit").empty_value(EmptyPolicy::Literal);
+        assert_eq!(
+            loader.load(&a()).unwrap(),
+            A {
+                o: Some("".into()),
+                s: "".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_ignore() {
+        let _v = vars!(
+            "EMPTYIGN_O" => "";
+            "EMPTYIGN_S" => "";
+        );
+        let loader = Loader::new("emptyign").empty_value(EmptyPolicy::Ignore);
+        assert_eq!(loader.load(&a()).unwrap(), a());
+    }
+
+    #[test]
+    fn test_validate_field() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            port: u32,
+        }
+
+        let loader = |port: &str| {
+            Loader::new("validate")
+                .source(
+                    vec![("VALIDATE_PORT".to_string(), port.to_string())]
+                        .into_iter()
+                        .collect::<HashMap<_, _>>(),
+                )
+                .validate_field("port", |value| match value.as_u64() {
+                    Some(1..=65535) => Ok(()),
+                    _ => Err("port out of range".into()),
+                })
+        };
+
+        assert_eq!(
+            loader("8080").load(&B::default()).unwrap(),
+            B { port: 8080 }
+        );
+        assert_eq!(
+            loader("70000").load(&B::default()),
+            Err(Error::UnpackError(
+                "VALIDATE_PORT".into(),
+                "port out of range".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_coerce_path() {
+        use std::path::PathBuf;
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            data: PathBuf,
+            cache: PathBuf,
+        }
+
+        let source = source(&[
+            ("HOME", "/home/eload"),
+            ("COERCEPATH_DATA", "~/data"),
+            ("COERCEPATH_CACHE", "$HOME/.cache/${APP}"),
+            ("APP", "eload"),
+        ]);
+
+        let b = Loader::new("coercepath")
+            .source(source)
+            .coerce("data", Coercion::Path)
+            .coerce("cache", Coercion::Path)
+            .load(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                data: "/home/eload/data".into(),
+                cache: "/home/eload/.cache/eload".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_transform() {
+        use std::path::PathBuf;
+
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct A {
+            http: Http,
+        }
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct Http {
+            name: String,
+            root: PathBuf,
+        }
+
+        let a = A {
+            http: Http {
+                name: "b".into(),
+                root: PathBuf::from("/"),
+            },
+        };
+
+        let _v = vars!(
+            "TRANSFORM_HTTP_NAME" => "  SPACED  ";
+            "TRANSFORM_HTTP_ROOT" => "~/www";
+        );
+        let loader = Loader::new("transform")
+            .transform("http.name", |s| s.trim().to_lowercase())
+            .transform("http.root", |s| s.replacen("~", "/home/eload", 1));
+        assert_eq!(
+            loader.load(&a).unwrap(),
+            A {
+                http: Http {
+                    name: "spaced".into(),
+                    root: PathBuf::from("/home/eload/www"),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_parallel_loaders() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+        }
+
+        let warnings1 = Arc::new(Mutex::new(Vec::new()));
+        let warnings2 = Arc::new(Mutex::new(Vec::new()));
+        let w1 = warnings1.clone();
+        let w2 = warnings2.clone();
+        let loader1 = Loader::new("one")
+            .source(source(&[("ONE_A", "1"), ("ONE_B", "1")]))
+            .warning_sink(move |w| w1.lock().unwrap().push(w.clone()));
+        let loader2 = Loader::new("two")
+            .source(source(&[("TWO_A", "2"), ("ONE_A", "3")]))
+            .warning_sink(move |w| w2.lock().unwrap().push(w.clone()));
+
+        // Loaders with their own sources don't read the process environment.
+        let _v = vars!(
+            "ONE_A" => "9";
+            "TWO_A" => "9";
+        );
+        std::thread::scope(|s| {
+            let b1 = s.spawn(|| loader1.load(&B::default()));
+            let b2 = s.spawn(|| loader2.load(&B::default()));
+            assert_eq!(b1.join().unwrap().unwrap(), B { a: 1 });
+            assert_eq!(b2.join().unwrap().unwrap(), B { a: 2 });
+        });
+
+        assert_eq!(
+            *warnings1.lock().unwrap(),
+            vec![Warning::UnusedVar("ONE_B".into())]
+        );
+        assert!(warnings2.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reuse_loader() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Tenant {
+            name: String,
+            port: u16,
+            hosts: Vec<String>,
+        }
+
+        let loader = Loader::new("tenant")
+            .source(source(&[("TENANT_PORT", "8080"), ("TENANT_HOSTS_1", "y")]));
+        for name in &["a", "b"] {
+            let tenant = Tenant {
+                name: name.to_string(),
+                port: 80,
+                hosts: vec!["x".into(), "x".into()],
+            };
+            assert_eq!(
+                loader.load(&tenant).unwrap(),
+                Tenant {
+                    name: name.to_string(),
+                    port: 8080,
+                    hosts: vec!["x".into(), "y".into()],
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_name_fn() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            http: Http,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+            host: String,
+        }
+
+        let source = source(&[
+            ("legacy.http.port", "8080"),
+            ("HTTP_HOST", "unused"),
+            ("legacy.other", "unused"),
+        ]);
+
+        let loader = Loader::new("legacy")
+            .source(source)
+            .name_fn(|segments| segments.join("."));
+        let (b, warnings) = loader.load_collect_warnings(&B::default()).unwrap();
+        assert_eq!(
+            b,
+            B {
+                http: Http {
+                    port: 8080,
+                    host: "".into()
+                }
+            }
+        );
+        assert_eq!(warnings, vec![Warning::UnusedVar("legacy.other".into())]);
+    }
+
+    #[test]
+    fn test_get_field() {
+        let _v = vars!(
+            "GETFIELD_HTTP_PORT" => "8080";
+        );
+        assert_eq!(
+            crate::get_field::<u16>("getfield", &["http", "port"]).unwrap(),
+            Some(8080)
+        );
+        assert_eq!(
+            crate::get_field::<u16>("getfield", &["http", "timeout"]).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_uppercase() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            straße: u32,
+        }
+
+        let source = source(&[("UPPER_STRAßE", "1"), ("UPPER_STRASSE", "2")]);
+
+        let loader = Loader::new("upper").source(source.clone());
+        assert_eq!(loader.load(&B::default()).unwrap(), B { straße: 1 });
+        let loader = Loader::new("upper").source(source).unicode_uppercase(true);
+        assert_eq!(loader.load(&B::default()).unwrap(), B { straße: 2 });
+    }
+
+    #[test]
+    fn test_report_fast_path() {
+        #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+        struct B {
+            a: u32,
+            #[serde(deserialize_with = "fail")]
+            b: u32,
+        }
+
+        fn fail<'de, D: serde::Deserializer<'de>>(_: D) -> std::result::Result<u32, D::Error> {
+            Err(serde::de::Error::custom("deserialized"))
+        }
+
+        // `B` fails to deserialize, so the values are returned without deserializing.
+        let loader = Loader::new("fastpath").source(HashMap::new());
+        let (b, report) = loader.load_with_report(B { a: 1, b: 2 }).unwrap();
+        assert_eq!(b, B { a: 1, b: 2 });
+        assert!(report.applied.is_empty());
+        assert_eq!(report.checked, vec!["FASTPATH_A", "FASTPATH_B"]);
+        assert_eq!(loader.load_cloned(&b).unwrap(), B { a: 1, b: 2 });
+        assert!(loader.load(&b).is_err());
+
+        let loader = Loader::new("fastpath").source(source(&[("FASTPATH_A", "3")]));
+        match loader.load_with_report(B { a: 1, b: 2 }) {
+            Err(crate::Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+        match loader.load_cloned(&B { a: 1, b: 2 }) {
+            Err(crate::Error::Deserialize(_)) => {}
+            r => panic!("unexpected result: {:?}", r),
+        }
+
+        // Removing an entry changes the value as well.
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct C {
+            limits: BTreeMap<String, u32>,
+        }
+
+        let c = C {
+            limits: vec![("cpu".to_string(), 1), ("mem".to_string(), 2)]
+                .into_iter()
+                .collect(),
+        };
+        let loader = Loader::new("fastpath")
+            .source(source(&[("FASTPATH_LIMITS_CPU", "__DELETE__")]))
+            .delete_sentinel("__DELETE__");
+        let (c, _) = loader.load_with_report(c).unwrap();
+        assert_eq!(c.limits, vec![("mem".to_string(), 2)].into_iter().collect());
+    }
+
+    #[test]
+    fn test_expand_refs() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            url: String,
+            ratio: String,
+        }
+
+        let source = source(&[
+            ("REFS_URL", "http://%HOST%:8080"),
+            ("REFS_RATIO", "100%%"),
+            ("HOST", "example.com"),
+        ]);
+
+        let loader = Loader::new("refs")
+            .source(source)
+            .expand_refs(RefSyntax::Windows);
+        assert_eq!(
+            loader.load(&B::default()).unwrap(),
+            B {
+                url: "http://example.com:8080".into(),
+                ratio: "100%".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_load_lenient() {
+        #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+        struct B {
+            host: String,
+            port: u16,
+            debug: bool,
+            ratio: f64,
+        }
+
+        let source = source(&[
+            ("LENIENT_HOST", "example.com"),
+            ("LENIENT_PORT", "http"),
+            ("LENIENT_DEBUG", "[1, 2]"),
+        ]);
+
+        let base = B {
+            port: 80,
+            ..B::default()
+        };
+        let (b, errors) = Loader::new("lenient").source(source).load_lenient(&base);
+        assert_eq!(
+            b,
+            B {
+                host: "example.com".into(),
+                port: 80,
+                ..B::default()
+            }
+        );
+        let names: Vec<_> = errors
+            .iter()
+            .map(|e| match e {
+                Error::UnpackError(name, _) => name.as_str(),
+                e => panic!("unexpected error: {:?}", e),
+            })
+            .collect();
+        assert_eq!(names, vec!["LENIENT_PORT", "LENIENT_DEBUG"]);
+    }
+
+    #[test]
+    fn test_prefix_aliases() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let source = source(&[
+            ("OLD_HOST", "old.example.com"),
+            ("OLD_PORT", "80"),
+            ("NEW_PORT", "8080"),
+        ]);
+
+        let (b, warnings) = Loader::new("new")
+            .source(source)
+            .prefix_aliases(&["old"])
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "old.example.com".into(),
+                port: 8080,
+            }
+        );
+        assert_eq!(
+            warnings,
+            vec![Warning::DeprecatedPrefix(
+                "OLD_HOST".into(),
+                "NEW_HOST".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_include_var() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+            user: String,
+        }
+
+        let source = source(&[
+            ("INC_HOST", "example.com"),
+            ("INC_PORT", "80"),
+            ("INC_INCLUDE", "extra, local"),
+            ("EXTRA_PORT", "8080"),
+            ("EXTRA_USER", "extra"),
+            ("EXTRA_INCLUDE", "inc"),
+            ("LOCAL_USER", "local"),
+            ("LOCAL_INCLUDE", "extra"),
+        ]);
+
+        let (b, warnings) = Loader::new("inc")
+            .source(source)
+            .include_var("include")
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "example.com".into(),
+                port: 8080,
+                user: "local".into(),
+            }
+        );
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn test_dynamic_map() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Tls {
+            cert: String,
+        }
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Service {
+            port: u16,
+            tls: Tls,
+        }
+
+        let mut base = BTreeMap::new();
+        base.insert("api".to_string(), Service::default());
+
+        let (services, warnings) = Loader::new("dyn")
+            .source(source(&[
+                ("DYN_API_PORT", "80"),
+                ("DYN_WEB_PORT", "8080"),
+                ("DYN_WEB_TLS_CERT", "web.pem"),
+                ("DYN_MY_DB_TLS_CERT", "db.pem"),
+            ]))
+            .dynamic_map::<Service>("")
+            .load_collect_warnings(&base)
+            .unwrap();
+        let service = |port, cert: &str| Service {
+            port,
+            tls: Tls { cert: cert.into() },
+        };
+        assert_eq!(
+            services,
+            vec![
+                ("api".to_string(), service(80, "")),
+                ("my_db".to_string(), service(0, "db.pem")),
+                ("web".to_string(), service(8080, "web.pem")),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(warnings, vec![]);
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            services: HashMap<String, Service>,
+        }
+
+        let source = source(&[("DYN_SERVICES_WEB_PORT", "8080")]);
+        let b = Loader::new("dyn")
+            .source(source)
+            .dynamic_map::<Service>("services")
+            .load(&B::default())
+            .unwrap();
+        assert_eq!(b.services["web"], service(8080, ""));
+    }
+
+    #[test]
+    fn test_negation_prefix() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+        struct B {
+            tls: bool,
+            verbose: bool,
+        }
+
+        let base = B {
+            tls: true,
+            verbose: false,
+        };
+        let loader = |vars: &[(&str, &str)]| {
+            Loader::new("neg")
+                .source(source(vars))
+                .negation_prefix("NO_")
+        };
+
+        let (b, warnings) = loader(&[("NEG_NO_TLS", "1"), ("NEG_NO_VERBOSE", "0")])
+            .load_collect_warnings(&base)
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                tls: false,
+                verbose: false,
+            }
+        );
+        assert_eq!(warnings, vec![]);
+
+        let (b, warnings) = loader(&[("NEG_TLS", "true"), ("NEG_NO_TLS", "1")])
+            .load_collect_warnings(&base)
+            .unwrap();
+        assert!(b.tls);
+        assert_eq!(
+            warnings,
+            vec![Warning::NegationConflict(
+                "NEG_TLS".into(),
+                "NEG_NO_TLS".into()
+            )]
+        );
+
+        assert_eq!(
+            loader(&[("NEG_NO_TLS", "maybe")]).load(&base),
+            Err(Error::UnpackError("NEG_NO_TLS".into(), "maybe".into()))
+        );
+    }
+
+    #[test]
+    fn test_log_summary() {
+        use std::sync::{Mutex, Once};
+
+        static INSTALL: Once = Once::new();
+        static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+        struct Capture;
+
+        impl Log for Capture {
+            fn enabled(&self, metadata: &Metadata) -> bool {
+                metadata.target() == "eload" && metadata.level() <= Level::Debug
+            }
+
+            fn log(&self, record: &Record) {
+                if self.enabled(record.metadata()) {
+                    LOGS.lock().unwrap().push(record.args().to_string());
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        // The logger is global, so it is installed once and the records are
+        // filtered by the target of the crate and the prefix of this test.
+        INSTALL.call_once(|| {
+            if log::set_logger(&Capture).is_ok() {
+                log::set_max_level(LevelFilter::Debug);
+            }
+        });
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let source = source(&[("SUMMARY_PORT", "80"), ("SUMMARY_UNKNOWN", "1")]);
+        Loader::new("summary")
+            .source(source)
+            .log_summary(true)
+            .warning_sink(|_| {})
+            .load(&B::default())
+            .unwrap();
+
+        let logs: Vec<_> = LOGS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|log| log.contains("SUMMARY"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            logs,
+            vec![
+                "loaded environment variables SUMMARY: 2 checked, 1 applied, 0 ambiguous, 1 unused"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_catch_all() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            extra: HashMap<String, String>,
+        }
+
+        let source = source(&[
+            ("CATCH_HOST", "example.com"),
+            ("CATCH_CUSTOM_THING", "1"),
+            ("CATCH_OTHER", "a b"),
+            ("UNRELATED", "x"),
+        ]);
+
+        let (b, warnings) = Loader::new("catch")
+            .source(source)
+            .catch_all("extra")
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(b.host, "example.com");
+        assert_eq!(
+            b.extra,
+            vec![
+                ("CUSTOM_THING".to_string(), "1".to_string()),
+                ("OTHER".to_string(), "a b".to_string()),
+            ]
+            .into_iter()
+            .collect()
+        );
+        assert_eq!(warnings, vec![]);
+    }
+
+    #[test]
+    fn test_null_sentinel() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            o1: Option<String>,
+            o2: Option<String>,
+        }
+
+        let base = B {
+            o1: Some("a".into()),
+            o2: Some("b".into()),
+        };
+        let source = || -> HashMap<String, String> {
+            vec![("SENTINEL_O1".to_string(), "__NULL__".to_string())]
+                .into_iter()
+                .collect()
+        };
+
+        assert_eq!(
+            Loader::new("sentinel")
+                .source(source())
+                .null_sentinel("__NULL__")
+                .load(&base)
+                .unwrap(),
+            B {
+                o1: None,
+                o2: Some("b".into()),
+            }
+        );
+        assert_eq!(
+            Loader::new("sentinel")
+                .source(source())
+                .load(&base)
+                .unwrap(),
+            B {
+                o1: Some("__NULL__".into()),
+                o2: Some("b".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_formats_load_identically() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+        struct B {
+            name: String,
+            ratio: f64,
+            db: Db,
+            tags: Vec<String>,
+            limits: BTreeMap<String, u32>,
+            user: Option<String>,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+        struct Db {
+            host: String,
+            port: u16,
+        }
+
+        let source = || -> HashMap<String, String> {
+            source(&[
+                ("FORMATS_NAME", "\"app\""),
+                ("FORMATS_RATIO", "0.25"),
+                ("FORMATS_DB", "{\"host\": \"db\", \"port\": 5432}"),
+                ("FORMATS_TAGS", "[\"a\", \"b\"]"),
+                ("FORMATS_LIMITS", "{\"cpu\": 2}"),
+                ("FORMATS_USER", "null"),
+            ])
+        };
+        let base = B {
+            user: Some("root".into()),
+            ..B::default()
+        };
+
+        let yaml = Loader::new("formats")
+            .source(source())
+            .format(Format::Yaml)
+            .load(&base)
+            .unwrap();
+        let json = Loader::new("formats")
+            .source(source())
+            .format(Format::Json)
+            .load(&base)
+            .unwrap();
+        assert_eq!(yaml, json);
+        assert_eq!(json.db.port, 5432);
+        assert_eq!(json.user, None);
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let loader = |policy| {
+            Loader::new("conflict")
+                .source(source(&[
+                    ("CONFLICT_HOST", "env.local"),
+                    ("CONFLICT_PORT", "80"),
+                ]))
+                .conflict_policy(policy)
+        };
+        let doc = "host: file.local\nport: 80\n";
+
+        assert_eq!(
+            loader(ConflictPolicy::Allow)
+                .load_patch(&B::default(), doc.as_bytes())
+                .unwrap(),
+            B {
+                host: "env.local".into(),
+                port: 80,
+            }
+        );
+        // The port is set to the same value, which is no conflict.
+        assert_eq!(
+            loader(ConflictPolicy::Error).load_patch(&B::default(), doc.as_bytes()),
+            Err(Error::Conflict("CONFLICT_HOST".into()))
+        );
+
+        let warnings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = warnings.clone();
+        loader(ConflictPolicy::Warn)
+            .warning_sink(move |w| sink.lock().unwrap().push(w.clone()))
+            .load_patch(&B::default(), doc.as_bytes())
+            .unwrap();
+        assert_eq!(
+            *warnings.lock().unwrap(),
+            vec![Warning::Conflict("CONFLICT_HOST".into())]
+        );
+    }
+
+    #[test]
+    fn test_load_with_file_var() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let file = std::env::temp_dir().join(format!("eload-file-var-{}.yaml", std::process::id()));
+        std::fs::write(&file, "host: file.local\nport: 80\n").unwrap();
+        let loader = |vars: Vec<(&str, String)>| {
+            Loader::new("filevar").source(
+                vars.into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect::<HashMap<_, _>>(),
+            )
+        };
+        let base = B {
+            host: "base.local".into(),
+            port: 0,
+        };
+
+        let b = loader(vec![
+            ("CONFIG_FILE", file.display().to_string()),
+            ("FILEVAR_PORT", "8080".into()),
+        ])
+        .load_with_file_var("CONFIG_FILE", &base)
+        .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "file.local".into(),
+                port: 8080,
+            }
+        );
+
+        let b = loader(vec![("FILEVAR_PORT", "8080".into())])
+            .load_with_file_var("CONFIG_FILE", &base)
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "base.local".into(),
+                port: 8080,
+            }
+        );
+
+        std::fs::remove_file(&file).unwrap();
+        assert!(matches!(
+            loader(vec![("CONFIG_FILE", file.display().to_string())])
+                .load_with_file_var("CONFIG_FILE", &base),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_base64_values() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            labels: HashMap<String, String>,
+            name: String,
+        }
+
+        let load = |enabled: bool, vars: &[(&str, &str)]| {
+            Loader::new("b64")
+                .source(source(vars))
+                .base64_values(enabled)
+                .load(&B::default())
+        };
+
+        // "app: \"web\"\ntier: 'front'\n"
+        let labels = "b64:YXBwOiAid2ViIgp0aWVyOiAnZnJvbnQnCg==";
+        let b = load(true, &[("B64_LABELS", labels), ("B64_NAME", "plain")]).unwrap();
+        let mut expected = HashMap::new();
+        expected.insert("app".to_string(), "web".to_string());
+        expected.insert("tier".to_string(), "front".to_string());
+        assert_eq!(b.labels, expected);
+        assert_eq!(b.name, "plain");
+
+        // Without the prefix or the option, the value is taken as is.
+        let b = load(true, &[("B64_NAME", "YXBw")]).unwrap();
+        assert_eq!(b.name, "YXBw");
+        let b = load(false, &[("B64_NAME", "b64:YXBw")]).unwrap();
+        assert_eq!(b.name, "b64:YXBw");
+
+        assert_eq!(
+            load(true, &[("B64_LABELS", "b64:not base64!")]),
+            Err(Error::UnpackError(
+                "B64_LABELS".into(),
+                "invalid base64 value".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_prefix_boundary() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            tls: bool,
+            labels: HashMap<String, String>,
+            extra: HashMap<String, String>,
+        }
+
+        let source = source(&[
+            ("APP_HOST", "example.com"),
+            ("APPLE_HOST", "apple.com"),
+            ("APPLE", "x"),
+            ("APPLABELS_A", "x"),
+            ("APPNO_TLS", "true"),
+            ("apple_tls", "true"),
+        ]);
+
+        for case_insensitive in &[false, true] {
+            let (b, report) = Loader::new("app")
+                .source(source.clone())
+                .case_insensitive(*case_insensitive)
+                .dynamic_map::<String>("labels")
+                .negation_prefix("no")
+                .load_with_report(B::default())
+                .unwrap();
+            assert_eq!(
+                b,
+                B {
+                    host: "example.com".into(),
+                    ..B::default()
+                }
+            );
+            assert!(report.checked.iter().all(|name| !name.starts_with("APPL")));
+            assert_eq!(report.warnings, vec![]);
+
+            let b = Loader::new("app")
+                .source(source.clone())
+                .case_insensitive(*case_insensitive)
+                .catch_all("extra")
+                .load(&B::default())
+                .unwrap();
+            assert_eq!(b.extra, HashMap::new());
+        }
+    }
+
+    #[test]
+    fn test_assignments_var() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+            tls: bool,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Db {
+            hosts: Vec<String>,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            http: Http,
+            db: Db,
+        }
+
+        let load = |set: &str| {
+            let source = source(&[("ASSIGN_HTTP_PORT", "80"), ("ASSIGN_SET", set)]);
+            let base = B {
+                db: Db {
+                    hosts: vec!["a".into(), "b".into()],
+                },
+                ..B::default()
+            };
+            Loader::new("assign")
+                .source(source)
+                .assignments_var("set")
+                .load_with_report(base)
+        };
+
+        let (b, report) = load("http.port=8080; http.tls=true;db.hosts[1]=x;").unwrap();
+        assert_eq!(
+            b,
+            B {
+                http: Http {
+                    port: 8080,
+                    tls: true,
+                },
+                db: Db {
+                    hosts: vec!["a".into(), "x".into()],
+                },
+            }
+        );
+        let paths: Vec<_> = report.applied.iter().map(|a| a.path.join(".")).collect();
+        assert_eq!(
+            paths,
+            vec!["http.port", "http.port", "http.tls", "db.hosts.1"]
+        );
+        assert_eq!(report.warnings, vec![]);
+
+        assert_eq!(
+            load("db.hosts[2]=x").unwrap_err(),
+            Error::UnpackError("ASSIGN_SET".into(), "db.hosts[2] not found".into())
+        );
+        for invalid in &["http.port", "http..port=1", "db.hosts[a]=x", "http.port="] {
+            assert_eq!(
+                load(invalid).unwrap_err(),
+                Error::UnpackError("ASSIGN_SET".into(), invalid.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_bool_tokens() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            tls: bool,
+            debug: bool,
+        }
+
+        let load = |vars: &[(&str, &str)]| {
+            Loader::new("tokens")
+                .source(source(vars))
+                .bool_tokens("tls", &["enabled", "on"], &["disabled", "off"])
+                .load(&B::default())
+        };
+
+        let b = load(&[("TOKENS_TLS", "Enabled"), ("TOKENS_DEBUG", "true")]).unwrap();
+        assert_eq!(
+            b,
+            B {
+                tls: true,
+                debug: true,
+            }
+        );
+        let b = load(&[("TOKENS_TLS", "disabled")]).unwrap();
+        assert!(!b.tls);
+        let b = load(&[("TOKENS_TLS", "true")]).unwrap();
+        assert!(b.tls);
+
+        assert!(load(&[("TOKENS_DEBUG", "enabled")]).is_err());
+    }
+
+    #[test]
+    fn test_int_grouping() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            max_bytes: u64,
+            name: String,
+        }
+
+        let loader = |sep, bytes: &str| {
+            Loader::new("grouping")
+                .source(source(&[
+                    ("GROUPING_MAX_BYTES", bytes),
+                    ("GROUPING_NAME", "a,b"),
+                ]))
+                .int_grouping(sep)
+        };
+
+        // Only integer fields are affected.
+        assert_eq!(
+            loader(',', "1,000,000").load(&B::default()).unwrap(),
+            B {
+                max_bytes: 1_000_000,
+                name: "a,b".into(),
+            }
+        );
+        assert_eq!(
+            loader('_', "1_000_000")
+                .load(&B::default())
+                .unwrap()
+                .max_bytes,
+            1_000_000
+        );
+        assert_eq!(
+            loader(',', "1,000_000").load(&B::default()),
+            Err(Error::UnpackError(
+                "GROUPING_MAX_BYTES".into(),
+                "1,000_000".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_delete_sentinel() {
+        #[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+        struct B {
+            limits: BTreeMap<String, u32>,
+        }
+
+        let base = B {
+            limits: vec![("cpu".to_string(), 1), ("memory".to_string(), 2)]
+                .into_iter()
+                .collect(),
+        };
+        let source = || -> HashMap<String, String> {
+            source(&[
+                ("DELETE_LIMITS_CPU", "__DELETE__"),
+                ("DELETE_LIMITS_MEMORY", "4"),
+            ])
+        };
+
+        let (b, warnings) = Loader::new("delete")
+            .source(source())
+            .delete_sentinel("__DELETE__")
+            .load_collect_warnings(&base)
+            .unwrap();
+        assert_eq!(
+            b.limits,
+            vec![("memory".to_string(), 4)].into_iter().collect()
+        );
+        assert_eq!(warnings, vec![]);
+
+        // The removal is in the report, and replaying it removes the entry again.
+        let loader = || {
+            Loader::new("delete")
+                .source(source())
+                .delete_sentinel("__DELETE__")
+        };
+        let (b, report) = loader().load_with_report(base.clone()).unwrap();
+        assert_eq!(
+            b.limits,
+            vec![("memory".to_string(), 4)].into_iter().collect()
+        );
+        let deleted: Vec<_> = report
+            .applied
+            .iter()
+            .filter(|a| a.operation == crate::Operation::Delete)
+            .collect();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].name, "DELETE_LIMITS_CPU");
+        assert_eq!(deleted[0].path, vec!["limits", "cpu"]);
+        assert_eq!(
+            report.to_patch(),
+            serde_yaml::from_str::<Value>("limits: {memory: 4}").unwrap()
+        );
+        let replayed = Loader::new("delete")
+            .source(report.to_env_pairs().into_iter().collect::<HashMap<_, _>>())
+            .delete_sentinel("__DELETE__")
+            .load(&base)
+            .unwrap();
+        assert_eq!(replayed, b);
+
+        // The sentinel is an ordinary value unless configured.
+        assert_eq!(
+            Loader::new("delete").source(source()).load(&base),
+            Err(Error::UnpackError(
+                "DELETE_LIMITS_CPU".into(),
+                "__DELETE__".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_allowlist() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+            user: String,
+        }
+
+        let source = source(&[
+            ("ALLOW_HOST", "example.com"),
+            ("ALLOW_PORT", "80"),
+            ("ALLOW_USER", "root"),
+            ("ALLOW_USER_FILE", "/etc/passwd"),
+        ]);
+
+        let (b, report) = Loader::new("allow")
+            .source(source)
+            .allowlist(&["ALLOW_HOST", "ALLOW_PORT"])
+            .load_with_report(B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "example.com".into(),
+                port: 80,
+                user: String::new(),
+            }
+        );
+        assert_eq!(report.checked, vec!["ALLOW_HOST", "ALLOW_PORT"]);
+    }
+
+    #[test]
+    fn test_scope() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Http {
+            port: u16,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            name: String,
+            http: Http,
+        }
+
+        let source = source(&[
+            ("SCOPE_NAME", "app"),
+            ("SCOPE_HTTP_PORT", "8080"),
+            ("SCOPE_HTTP_HOST", "localhost"),
+            ("SCOPE_DB_HOST", "localhost"),
+        ]);
+
+        let loader = Loader::new("scope").source(source).scope(&["http"]);
+        let (b, warnings) = loader.load_collect_warnings(&B::default()).unwrap();
+        assert_eq!(
+            b,
+            B {
+                name: String::new(),
+                http: Http { port: 8080 },
+            }
+        );
+        assert_eq!(warnings, vec![Warning::UnusedVar("SCOPE_HTTP_HOST".into())]);
+
+        let names: Vec<_> = loader
+            .describe(&b)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(names, vec!["SCOPE_HTTP", "SCOPE_HTTP_PORT"]);
+    }
+
+    #[test]
+    fn test_case_insensitive() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let source = source(&[
+            ("CASE_host", "lower"),
+            ("CASE_HOST", "upper"),
+            ("Case_Port", "8080"),
+        ]);
+
+        let (b, warnings) = Loader::new("case")
+            .source(source)
+            .case_insensitive(true)
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "upper".into(),
+                port: 8080,
+            }
+        );
+        assert_eq!(
+            warnings,
+            vec![Warning::CaseCollision(
+                "CASE_HOST".into(),
+                vec!["CASE_HOST".into(), "CASE_host".into()]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_overlay() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            port: u16,
+        }
+
+        let loader = |vars: &[(&str, &str)]| {
+            Loader::new("overlay")
+                .source(source(vars))
+                .overlay(
+                    "prod",
+                    source(&[
+                        ("OVERLAY_HOST", "prod.example.com"),
+                        ("OVERLAY_PORT", "443"),
+                    ]),
+                )
+                .overlay("dev", source(&[("OVERLAY_HOST", "localhost")]))
+        };
+
+        let (b, warnings) = loader(&[("OVERLAY_ENV", "prod"), ("OVERLAY_PORT", "8443")])
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                host: "prod.example.com".into(),
+                port: 8443,
+            }
+        );
+        assert!(warnings.is_empty());
+
+        let b = loader(&[]).load(&B::default()).unwrap();
+        assert_eq!(b, B::default());
+    }
+
+    #[test]
+    fn test_field_prefix() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Server {
+            port: u16,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct Database {
+            host: String,
+        }
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            name: String,
+            server: Server,
+            database: Database,
+        }
+
+        let source = source(&[
+            ("APP_NAME", "app"),
+            ("SRV_PORT", "8080"),
+            ("DB_HOST", "db.example.com"),
+            ("APP_SERVER_PORT", "80"),
+        ]);
+
+        let (b, warnings) = Loader::new("app")
+            .source(source)
+            .field_prefix("server", "srv")
+            .field_prefix("database", "db")
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                name: "app".into(),
+                server: Server { port: 8080 },
+                database: Database {
+                    host: "db.example.com".into()
+                },
+            }
+        );
+        assert_eq!(warnings, vec![Warning::UnusedVar("APP_SERVER_PORT".into())]);
+    }
+
+    #[test]
+    fn test_trim() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            port: u16,
+            name: String,
+            banner: String,
+        }
+
+        let source = || -> HashMap<_, _> {
+            source(&[
+                ("TRIM_PORT", " 8080 "),
+                ("TRIM_NAME", " app\n"),
+                ("TRIM_BANNER", "  hi  "),
+            ])
+        };
+        let loader = |trim| {
+            Loader::new("trim")
+                .source(source())
+                .coerce("name", Coercion::RawString)
+                .coerce("banner", Coercion::RawString)
+                .transform("banner", |s| s)
+                .trim(trim)
+        };
+
+        assert_eq!(
+            loader(true).load(&B::default()).unwrap(),
+            B {
+                port: 8080,
+                name: "app".into(),
+                banner: "  hi  ".into(),
+            }
+        );
+        assert_eq!(
+            loader(false).load(&B::default()).unwrap(),
+            B {
+                port: 8080,
+                name: " app\n".into(),
+                banner: "  hi  ".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolver() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            host: String,
+            password: String,
+            token: String,
+        }
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let loader = Loader::new("resolve")
+            .source(source(&[
+                ("RESOLVE_PASSWORD", "vault:db"),
+                ("RESOLVE_TOKEN", "vault:missing"),
+            ]))
+            .skip(&["token"])
+            .resolver(move |_, value| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                match value.as_str() {
+                    "vault:db" => Ok("hunter2".into()),
+                    v => Err(format!("no secret {}", v)),
+                }
+            });
+        assert_eq!(
+            loader.load(&B::default()).unwrap(),
+            B {
+                password: "hunter2".into(),
+                ..B::default()
+            }
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let loader = Loader::new("resolve")
+            .source(source(&[("RESOLVE_PASSWORD", "vault:db")]))
+            .resolver(|_, value| Err(format!("no secret {}", value)));
+        match loader.load(&B::default()) {
+            Err(Error::UnpackError(path, _)) => assert_eq!(path, "RESOLVE_PASSWORD"),
+            r => panic!("unexpected result: {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_alias_var() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            database_url: String,
+            port: u16,
+        }
+
+        let source = source(&[
+            ("DATABASE_URL", "postgres://db"),
+            ("ALIAS_DATABASE_URL", "postgres://ignored"),
+            ("ALIAS_PORT", "8080"),
+        ]);
+
+        let (b, warnings) = Loader::new("alias")
+            .source(source)
+            .alias_var("database_url", "DATABASE_URL")
+            .load_collect_warnings(&B::default())
+            .unwrap();
+        assert_eq!(
+            b,
+            B {
+                database_url: "postgres://db".into(),
+                port: 8080,
+            }
+        );
+        assert_eq!(
+            warnings,
+            vec![Warning::UnusedVar("ALIAS_DATABASE_URL".into())]
+        );
+    }
+
+    #[test]
+    fn test_candidates() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct B {
+            database_url: String,
+        }
+
+        let load = |vars| {
+            Loader::new("cand")
+                .source(source(vars))
+                .candidates("database_url", &["APP_DATABASE_URL", "DATABASE_URL"])
+                .load_collect_warnings(&B::default())
+                .unwrap()
+        };
+
+        let (b, warnings) = load(&[("DATABASE_URL", "second")]);
+        assert_eq!(b.database_url, "second");
+        assert!(warnings.is_empty());
+
+        let (b, _) = load(&[("DATABASE_URL", "second"), ("APP_DATABASE_URL", "first")]);
+        assert_eq!(b.database_url, "first");
+    }
+}
@@ -0,0 +1,145 @@
+//! `figment` provider, enabled by the `figment` feature.
+//!
+//! Values are converted from `serde_yaml::Value` by serializing them, so mappings
+//! become dictionaries, sequences arrays, and null values empty values.
+
+use crate::{lookup, merge, Loader};
+use figment::value::{Dict, Map, Value};
+use figment::{Error, Metadata, Profile, Provider};
+use serde::Serialize;
+use serde_yaml::Mapping;
+
+/// `figment::Provider` of the environment variables recognized for a base value.
+///
+/// Only the fields set by environment variables are provided, so the values of
+/// other providers are kept for the rest.
+pub struct EnvProvider<T> {
+    loader: Loader,
+    base: T,
+}
+
+impl<T: Serialize> EnvProvider<T> {
+    /// Provide the environment variables starting with `pfx` for `base`.
+    pub fn new(pfx: &str, base: T) -> Self {
+        Self::with_loader(Loader::new(pfx), base)
+    }
+
+    /// Provide the environment variables recognized by `loader` for `base`.
+    pub fn with_loader(loader: Loader, base: T) -> Self {
+        Self { loader, base }
+    }
+
+    /// Value tree of the fields set by environment variables.
+    fn patch(&self) -> crate::Result<serde_yaml::Value> {
+        let (value, report) = self.loader.walk(&self.base)?.finish();
+        self.loader.warn(&report.warnings);
+
+        let mut patch = serde_yaml::Value::Mapping(Mapping::new());
+        for applied in report.applied {
+            // Entries can't be removed from the values of other providers.
+            let result = match applied.result() {
+                Some(result) => result.clone(),
+                None => continue,
+            };
+            // Elements of sequences are provided with the whole sequence.
+            let path = match lookup_seq(&value, &applied.path) {
+                Some(len) => &applied.path[..len],
+                None => &applied.path[..],
+            };
+            let mut node = if path.len() < applied.path.len() {
+                lookup(&value, path).cloned().unwrap_or_default()
+            } else {
+                result
+            };
+            for name in path.iter().rev() {
+                let mut map = Mapping::new();
+                map.insert(name.clone().into(), node);
+                node = serde_yaml::Value::Mapping(map);
+            }
+            merge(&mut patch, node);
+        }
+        Ok(patch)
+    }
+}
+
+/// Length of the prefix of `path` leading to the outermost sequence in `value`, if any.
+fn lookup_seq(value: &serde_yaml::Value, path: &[String]) -> Option<usize> {
+    (0..path.len()).find(|&i| {
+        matches!(
+            lookup(value, &path[..i]),
+            Some(serde_yaml::Value::Sequence(_))
+        )
+    })
+}
+
+impl<T: Serialize> Provider for EnvProvider<T> {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("eload environment variables")
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+        let patch = self.patch().map_err(|e| Error::from(e.to_string()))?;
+        let dict = Value::serialize(patch)?
+            .into_dict()
+            .ok_or_else(|| Error::from("not a dictionary".to_string()))?;
+        Ok(Profile::Default.collect(dict))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::source;
+    use figment::providers::Serialized;
+    use figment::Figment;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+    struct Http {
+        host: String,
+        port: u16,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq)]
+    struct A {
+        name: String,
+        http: Http,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn test_provider() {
+        let source = source(&[("FIG_HTTP_PORT", "8080"), ("FIG_TAGS_1", "z")]);
+
+        let defaults = A {
+            name: "default".into(),
+            http: Http {
+                host: "localhost".into(),
+                port: 80,
+            },
+            tags: vec!["a".into(), "b".into()],
+        };
+        let file = Serialized::defaults(A {
+            name: "file".into(),
+            ..defaults.clone()
+        });
+        let env = EnvProvider::with_loader(Loader::new("fig").source(source), defaults.clone());
+
+        let a: A = Figment::from(Serialized::defaults(defaults))
+            .merge(file)
+            .merge(env)
+            .extract()
+            .unwrap();
+        assert_eq!(
+            a,
+            A {
+                name: "file".into(),
+                http: Http {
+                    host: "localhost".into(),
+                    port: 8080,
+                },
+                tags: vec!["a".into(), "z".into()],
+            }
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use crate::{Error, Loader, Report, Result, Serializer, Warning};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_yaml::Value;
+use std::collections::{hash_map::DefaultHasher, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// Reloads a value from environment variables, detecting changes between reloads.
+///
+/// Useful for long-running services polling the environment, e.g. after a `SIGHUP`,
+/// to skip work when nothing has changed.
+pub struct Reloader<T> {
+    loader: Loader,
+    last: Option<Value>,
+    report: Report,
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Reloader<T> {
+    /// Reload the environment variables starting with `pfx`.
+    pub fn new(pfx: &str) -> Self {
+        Self::with_loader(Loader::new(pfx))
+    }
+
+    /// Reload the environment variables recognized by `loader`.
+    pub fn with_loader(loader: Loader) -> Self {
+        Self {
+            loader,
+            last: None,
+            report: Report::default(),
+            _t: PhantomData,
+        }
+    }
+
+    /// Load environment variables into the struct members of `base`.
+    ///
+    /// Returns `None` if the loaded value is the same as the one of the last reload,
+    /// comparing the values merged from `base` and the environment variables.
+    /// The first reload always returns the loaded value.
+    pub fn reload(&mut self, base: &T) -> Result<Option<T>> {
+        let (value, report) = self.loader.walk(base)?.finish();
+        self.loader.warn(&report.warnings);
+        self.report = report;
+        if self.last.as_ref() == Some(&value) {
+            return Ok(None);
+        }
+        let t = serde_yaml::from_value(value.clone()).map_err(Error::deserialize)?;
+        self.last = Some(value);
+        Ok(Some(t))
+    }
+
+    /// Report of the last reload.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+}
+
+impl Loader {
+    /// Fingerprint of the environment variables recognized for `t`, to cheaply tell
+    /// whether they changed since the last load, e.g. before reloading.
+    ///
+    /// The names and values of the variables checked for the fields, and of the other
+    /// variables under the prefix, are hashed, so unrelated variables don't change it.
+    /// So are the values the fields get through candidates, prefix aliases and overlays,
+    /// and the variable selecting the overlay.
+    /// Fingerprints are comparable within a build of the program only.
+    pub fn env_fingerprint<T: Serialize>(&self, t: &T) -> Result<u64> {
+        let value = serde_yaml::to_value(t).map_err(Error::serialize)?;
+        let mut ser = Serializer::new(self, &self.prefix, value);
+        // Invalid values are part of the fingerprint as well.
+        ser.errors = Some(Vec::new());
+        t.serialize(&mut ser).map_err(|e| ser.pack_context(e))?;
+        ser.include(t)?;
+
+        let mut names: BTreeSet<_> = ser.paths.iter().cloned().collect();
+        names.extend(
+            ser.unused()
+                .into_iter()
+                .filter_map(|warning| match warning {
+                    Warning::UnusedVar(name) => Some(name),
+                    _ => None,
+                }),
+        );
+        names.insert(self.overlay_var_name());
+        let mut hasher = DefaultHasher::new();
+        for name in names {
+            if let Some(val) = ser.lookup(&name)? {
+                (name, val).hash(&mut hasher);
+            }
+        }
+        let reads: BTreeSet<_> = std::mem::take(&mut ser.reads).into_iter().collect();
+        reads.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+}
+
+/// Fingerprint of the environment variables starting with `pfx` recognized for `t`.
+pub fn env_fingerprint<T: Serialize>(pfx: &str, t: &T) -> Result<u64> {
+    Loader::new(pfx).env_fingerprint(t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::source;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    struct A {
+        port: u16,
+        host: String,
+    }
+
+    #[test]
+    fn test_env_fingerprint() {
+        let base = A::default();
+        let fingerprint = |vars: &[(&str, &str)]| {
+            Loader::new("fingerprint")
+                .source(source(vars))
+                .env_fingerprint(&base)
+                .unwrap()
+        };
+
+        let fp = fingerprint(&[("FINGERPRINT_PORT", "80"), ("OTHER", "a")]);
+        assert_eq!(
+            fingerprint(&[("FINGERPRINT_PORT", "80"), ("OTHER", "a")]),
+            fp
+        );
+        assert_eq!(
+            fingerprint(&[("FINGERPRINT_PORT", "80"), ("OTHER", "b")]),
+            fp
+        );
+        assert_eq!(fingerprint(&[("FINGERPRINT_PORT", "80")]), fp);
+
+        assert_ne!(fingerprint(&[("FINGERPRINT_PORT", "81")]), fp);
+        assert_ne!(fingerprint(&[("FINGERPRINT_PORT", "x")]), fp);
+        assert_ne!(fingerprint(&[]), fp);
+        assert_ne!(
+            fingerprint(&[("FINGERPRINT_PORT", "80"), ("FINGERPRINT_HOST", "a")]),
+            fp
+        );
+        // Variables under the prefix matching no field are relevant too.
+        assert_ne!(
+            fingerprint(&[("FINGERPRINT_PORT", "80"), ("FINGERPRINT_TYPO", "a")]),
+            fp
+        );
+    }
+
+    #[test]
+    fn test_env_fingerprint_aliases_and_overlays() {
+        let base = A::default();
+        let fingerprint = |vars: &[(&str, &str)], overlay: &[(&str, &str)]| {
+            Loader::new("fpnew")
+                .source(source(vars))
+                .prefix_aliases(&["fpold"])
+                .overlay("prod", source(overlay))
+                .env_fingerprint(&base)
+                .unwrap()
+        };
+
+        // Values of deprecated prefixes.
+        let fp = fingerprint(&[("FPOLD_PORT", "80")], &[]);
+        assert_eq!(fingerprint(&[("FPOLD_PORT", "80")], &[]), fp);
+        assert_ne!(fingerprint(&[("FPOLD_PORT", "81")], &[]), fp);
+        assert_eq!(
+            fingerprint(&[("FPOLD_PORT", "80"), ("UNRELATED", "x")], &[]),
+            fp
+        );
+
+        // The overlay selector and the values of the selected overlay.
+        let overlay = [("FPNEW_HOST", "prod.local")];
+        let fp = fingerprint(&[("FPNEW_ENV", "prod")], &overlay);
+        assert_eq!(fingerprint(&[("FPNEW_ENV", "prod")], &overlay), fp);
+        assert_ne!(fingerprint(&[], &overlay), fp);
+        assert_ne!(fingerprint(&[("FPNEW_ENV", "dev")], &overlay), fp);
+        assert_ne!(
+            fingerprint(&[("FPNEW_ENV", "prod")], &[("FPNEW_HOST", "other.local")]),
+            fp
+        );
+    }
+
+    #[test]
+    fn test_reload() {
+        let base = A::default();
+
+        let mut reloader = Reloader::<A>::new("reload");
+        assert_eq!(reloader.reload(&base).unwrap(), Some(A::default()));
+        assert_eq!(reloader.reload(&base).unwrap(), None);
+
+        let loader = Loader::new("reload").source(source(&[("RELOAD_PORT", "80")]));
+        let mut reloader = Reloader::with_loader(loader);
+        assert_eq!(
+            reloader.reload(&base).unwrap(),
+            Some(A {
+                port: 80,
+                host: String::new(),
+            })
+        );
+        assert_eq!(reloader.reload(&base).unwrap(), None);
+        assert_eq!(reloader.report().applied.len(), 1);
+
+        // A different base changes the merged value.
+        let base = A {
+            port: 0,
+            host: "localhost".into(),
+        };
+        assert_eq!(
+            reloader.reload(&base).unwrap(),
+            Some(A {
+                port: 80,
+                host: "localhost".into(),
+            })
+        );
+    }
+}
@@ -0,0 +1,511 @@
+use crate::{describe::display, remove_entry, Warning};
+use serde_yaml::Value;
+
+/// Environment variable applied to a field.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Applied {
+    /// Name of the environment variable.
+    pub name: String,
+    /// Field names from the root to the field.
+    pub path: Vec<String>,
+    /// Parsed value of the environment variable.
+    pub value: Value,
+    /// Whether the field, or a field inside it, is marked as secret by `Loader::secret`.
+    pub secret: bool,
+    /// How the variable changes the field.
+    pub operation: Operation,
+}
+
+/// Change made by an environment variable to the field at the path of `Applied`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    /// The field is set to the value, or merged with it if a struct.
+    Set,
+    /// The bool field is set to `false`, as the variable with the prefix of
+    /// `Loader::negation_prefix` is truthy.
+    Negate,
+    /// The map entry is removed, as the variable is set to the sentinel of
+    /// `Loader::delete_sentinel`, which is the value.
+    Delete,
+    /// The value is pushed to the sequence by the variable with the suffix of
+    /// `Loader::append_suffix`. Holds the resulting sequence.
+    Append(Value),
+    /// The sequence is truncated to the length, which is the value, by the `LEN` variable.
+    /// Holds the resulting sequence.
+    Truncate(Value),
+    /// The field is set to the value by an assignment in the variable of
+    /// `Loader::assignments_var`. Holds the path of the assignment, e.g. `db.hosts[0]`.
+    Assign(String),
+    /// The field is set to the variable as is, without parsing, as coerced by
+    /// `Loader::coerce`, or as an entry of the map of `Loader::catch_all`.
+    SetRaw,
+}
+
+impl Applied {
+    /// Value of the field at `path` after the variable is applied, `None` if removed.
+    pub(crate) fn result(&self) -> Option<&Value> {
+        match &self.operation {
+            Operation::Delete => None,
+            Operation::Append(seq) | Operation::Truncate(seq) => Some(seq),
+            _ => Some(&self.value),
+        }
+    }
+
+    /// Value of the variable reproducing the change, `redacted` if secret.
+    fn var_value(&self, redact: bool) -> String {
+        if redact && self.secret {
+            return "***".into();
+        }
+        match (&self.operation, &self.value) {
+            (Operation::Negate, _) => "true".into(),
+            (Operation::Delete, value) | (Operation::SetRaw, value) => display(value),
+            // Strings parsed as other values, e.g. `8080`, are quoted to stay strings.
+            (_, Value::String(s))
+                if !s.contains('\n')
+                    && serde_yaml::from_str::<Value>(s).ok().as_ref() != Some(&self.value) =>
+            {
+                serde_json::to_string(s).unwrap_or_else(|_| s.clone())
+            }
+            (_, value) => display(value),
+        }
+    }
+}
+
+/// Summary of a load.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// Environment variables applied, in the order of application.
+    pub applied: Vec<Applied>,
+    /// Names of the environment variables checked, in the order of checking.
+    pub checked: Vec<String>,
+    /// Warnings found while loading.
+    pub warnings: Vec<Warning>,
+}
+
+impl Report {
+    /// Shell script exporting the applied environment variables, one `export NAME='value'`
+    /// line for each of `to_env_pairs`, to reproduce the load when sourced.
+    pub fn to_shell_exports(&self) -> String {
+        self.exports(false)
+    }
+
+    /// Same as `to_shell_exports` but with the values of secret fields redacted as `***`.
+    pub fn to_shell_exports_redacted(&self) -> String {
+        self.exports(true)
+    }
+
+    /// Names and values of the applied environment variables, e.g. for `Command::envs`
+    /// to pass the loaded config on to child processes. Values are in flow style,
+    /// with strings unquoted unless parsed as other values, e.g. `"8080"`, so they
+    /// load back to the same values with the same loader.
+    ///
+    /// Variables appending to and truncating sequences keep the element and the length,
+    /// and the assignments of `Loader::assignments_var` are joined into one variable.
+    pub fn to_env_pairs(&self) -> Vec<(String, String)> {
+        self.pairs(false)
+    }
+
+    /// Sparse tree of the applied values at their paths, e.g. `{http: {port: 8080}}`,
+    /// to store the overrides rather than the whole config.
+    ///
+    /// Values applied later replace or, for fields and elements inside them, update
+    /// the earlier ones. Elements of sequences applied by index alone are under
+    /// their indices as mapping keys.
+    ///
+    /// Removed map entries can't be expressed by merging, so they are left out of the patch,
+    /// along with the values applied to them before. The variables of `to_env_pairs`
+    /// reproduce them with the same `Loader::delete_sentinel`.
+    pub fn to_patch(&self) -> Value {
+        let mut patch = Value::Mapping(Default::default());
+        for applied in &self.applied {
+            let value = match applied.result() {
+                Some(value) => value,
+                None => {
+                    remove_entry(&mut patch, &applied.path);
+                    continue;
+                }
+            };
+            let mut node = &mut patch;
+            for key in &applied.path {
+                let index = match node {
+                    Value::Sequence(seq) => key.parse::<usize>().ok().filter(|i| *i < seq.len()),
+                    _ => None,
+                };
+                if index.is_none() && !node.is_mapping() {
+                    *node = Value::Mapping(Default::default());
+                }
+                node = match (node, index) {
+                    (Value::Sequence(seq), Some(i)) => &mut seq[i],
+                    (Value::Mapping(map), _) => map
+                        .entry(Value::String(key.clone()))
+                        .or_insert(Value::Mapping(Default::default())),
+                    _ => unreachable!("not a container"),
+                };
+            }
+            *node = value.clone();
+        }
+        patch
+    }
+
+    /// Append the entries of `other`, e.g. the report of the load of another component.
+    /// Names checked and warnings already in `self` are not added again.
+    pub fn merge(&mut self, other: Report) {
+        self.applied.extend(other.applied);
+        for name in other.checked {
+            if !self.checked.contains(&name) {
+                self.checked.push(name);
+            }
+        }
+        for warning in other.warnings {
+            if !self.warnings.contains(&warning) {
+                self.warnings.push(warning);
+            }
+        }
+    }
+
+    /// One-line summary of the counts of the load.
+    pub(crate) fn summary(&self) -> String {
+        let count = |f: fn(&Warning) -> bool| self.warnings.iter().filter(|w| f(w)).count();
+        format!(
+            "{} checked, {} applied, {} ambiguous, {} unused",
+            self.checked.len(),
+            self.applied.len(),
+            count(|w| matches!(w, Warning::Ambiguous(_))),
+            count(|w| matches!(w, Warning::UnusedVar(_))),
+        )
+    }
+
+    fn pairs(&self, redact: bool) -> Vec<(String, String)> {
+        let mut pairs: Vec<(String, String)> = Vec::new();
+        for applied in &self.applied {
+            let value = applied.var_value(redact);
+            match &applied.operation {
+                Operation::Assign(path) => {
+                    let assignment = format!("{}={}", path, value);
+                    match pairs.last_mut() {
+                        Some((name, list)) if *name == applied.name => {
+                            list.push(';');
+                            list.push_str(&assignment);
+                        }
+                        _ => pairs.push((applied.name.clone(), assignment)),
+                    }
+                }
+                _ => pairs.push((applied.name.clone(), value)),
+            }
+        }
+        pairs
+    }
+
+    fn exports(&self, redact: bool) -> String {
+        self.pairs(redact)
+            .into_iter()
+            .map(|(name, value)| format!("export {}={}\n", name, shell_quote(&value)))
+            .collect()
+    }
+}
+
+impl Extend<Report> for Report {
+    fn extend<I: IntoIterator<Item = Report>>(&mut self, iter: I) {
+        for report in iter {
+            self.merge(report);
+        }
+    }
+}
+
+/// Quote `s` in single quotes for POSIX shells.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::source;
+    use crate::{Loader, Warning};
+    use serde::{Deserialize, Serialize};
+    use serde_yaml::Value;
+    use std::collections::HashMap;
+
+    #[derive(Serialize, Deserialize, Default)]
+    struct A {
+        motd: String,
+        ports: Vec<u32>,
+        password: String,
+    }
+
+    #[test]
+    fn test_to_shell_exports() {
+        let source = source(&[
+            ("EXPORTS_MOTD", "it's a value"),
+            ("EXPORTS_PORTS", "[1, 2]"),
+            ("EXPORTS_PASSWORD", "hunter2"),
+        ]);
+
+        let (_, report) = Loader::new("exports")
+            .source(source)
+            .secret("password")
+            .load_with_report(A::default())
+            .unwrap();
+        assert_eq!(
+            report.to_shell_exports(),
+            "export EXPORTS_MOTD='it'\\''s a value'\n\
+             export EXPORTS_PORTS='[1,2]'\n\
+             export EXPORTS_PASSWORD='hunter2'\n"
+        );
+        assert_eq!(
+            report.to_shell_exports_redacted(),
+            "export EXPORTS_MOTD='it'\\''s a value'\n\
+             export EXPORTS_PORTS='[1,2]'\n\
+             export EXPORTS_PASSWORD='***'\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_to_env_pairs() {
+        let source = source(&[("PAIRS_MOTD", "hello world"), ("PAIRS_PORTS", "[1, 2]")]);
+
+        let (a, report) = Loader::new("pairs")
+            .source(source)
+            .load_with_report(A::default())
+            .unwrap();
+        let pairs = report.to_env_pairs();
+        assert_eq!(
+            pairs,
+            vec![
+                ("PAIRS_MOTD".to_string(), "hello world".to_string()),
+                ("PAIRS_PORTS".to_string(), "[1,2]".to_string()),
+            ]
+        );
+
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg("printf '%s;%s' \"$PAIRS_MOTD\" \"$PAIRS_PORTS\"")
+            .env_clear()
+            .envs(pairs.clone())
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "hello world;[1,2]"
+        );
+
+        // The pairs load back to the same values.
+        let b = Loader::new("pairs")
+            .source(pairs.into_iter().collect::<HashMap<_, _>>())
+            .load(&A::default())
+            .unwrap();
+        assert_eq!((b.motd, b.ports), (a.motd, a.ports));
+    }
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    struct Http {
+        port: u16,
+        host: String,
+    }
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+    struct B {
+        name: String,
+        flag: String,
+        tls: bool,
+        tags: Vec<String>,
+        hosts: Vec<String>,
+        http: Http,
+    }
+
+    fn round_trip_base() -> B {
+        B {
+            tls: true,
+            tags: vec!["a".into(), "b".into()],
+            hosts: vec!["x".into(), "y".into(), "z".into()],
+            ..B::default()
+        }
+    }
+
+    fn round_trip_loader(source: HashMap<String, String>) -> Loader {
+        Loader::new("roundtrip")
+            .source(source)
+            .negation_prefix("NO_")
+            .assignments_var("set")
+    }
+
+    #[test]
+    fn test_to_env_pairs_round_trip() {
+        let (b, report) = round_trip_loader(source(&[
+            ("ROUNDTRIP_NAME", "'8080'"),
+            ("ROUNDTRIP_FLAG", "'true'"),
+            ("ROUNDTRIP_NO_TLS", "1"),
+            ("ROUNDTRIP_TAGS_APPEND", "c"),
+            ("ROUNDTRIP_HOSTS_LEN", "2"),
+            ("ROUNDTRIP_SET", "http.port=8080;http.host='true'"),
+        ]))
+        .load_with_report(round_trip_base())
+        .unwrap();
+
+        let pairs = report.to_env_pairs();
+        assert_eq!(
+            pairs,
+            vec![
+                ("ROUNDTRIP_NAME".to_string(), "\"8080\"".to_string()),
+                ("ROUNDTRIP_FLAG".to_string(), "\"true\"".to_string()),
+                ("ROUNDTRIP_NO_TLS".to_string(), "true".to_string()),
+                ("ROUNDTRIP_TAGS_APPEND".to_string(), "c".to_string()),
+                ("ROUNDTRIP_HOSTS_LEN".to_string(), "2".to_string()),
+                (
+                    "ROUNDTRIP_SET".to_string(),
+                    "http.port=8080;http.host=\"true\"".to_string()
+                ),
+            ]
+        );
+
+        let c = round_trip_loader(pairs.into_iter().collect())
+            .load(&round_trip_base())
+            .unwrap();
+        assert_eq!(c, b);
+        assert_eq!(
+            c,
+            B {
+                name: "8080".into(),
+                flag: "true".into(),
+                tls: false,
+                tags: vec!["a".into(), "b".into(), "c".into()],
+                hosts: vec!["x".into(), "y".into()],
+                http: Http {
+                    port: 8080,
+                    host: "true".into(),
+                },
+            }
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_to_shell_exports_source() {
+        let (b, report) = round_trip_loader(source(&[
+            ("ROUNDTRIP_NAME", "it's 8080"),
+            ("ROUNDTRIP_FLAG", "'true'"),
+            ("ROUNDTRIP_TAGS_APPEND", "c"),
+            ("ROUNDTRIP_HOSTS_LEN", "1"),
+            ("ROUNDTRIP_SET", "http.port=8080"),
+        ]))
+        .load_with_report(round_trip_base())
+        .unwrap();
+        let exports = report.to_shell_exports();
+        assert!(exports.contains("export ROUNDTRIP_TAGS_APPEND='c'\n"));
+        assert!(exports.contains("export ROUNDTRIP_HOSTS_LEN='1'\n"));
+
+        // The variables of the sourced script load back to the same value.
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("{}env", exports))
+            .env_clear()
+            .output()
+            .unwrap();
+        let vars: HashMap<_, _> = String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .filter(|(name, _)| name.starts_with("ROUNDTRIP_"))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        assert_eq!(vars.len(), 5);
+        let c = round_trip_loader(vars).load(&round_trip_base()).unwrap();
+        assert_eq!(c, b);
+        assert_eq!(c.tags, vec!["a", "b", "c"]);
+        assert_eq!(c.hosts, vec!["x"]);
+    }
+
+    #[test]
+    fn test_to_patch() {
+        #[derive(Serialize, Deserialize, Default)]
+        struct Db {
+            host: String,
+            port: u16,
+            user: String,
+        }
+        #[derive(Serialize, Deserialize, Default)]
+        struct B {
+            name: String,
+            db: Db,
+            tags: Vec<String>,
+            http: HashMap<String, u16>,
+        }
+
+        let source = source(&[
+            ("PATCH_DB", "{host: db.local}"),
+            ("PATCH_DB_PORT", "5432"),
+            ("PATCH_TAGS", "[a, b]"),
+            ("PATCH_TAGS_1", "c"),
+        ]);
+
+        let base = B {
+            tags: vec!["x".into(), "y".into()],
+            ..B::default()
+        };
+        let (_, report) = Loader::new("patch")
+            .source(source)
+            .load_with_report(base)
+            .unwrap();
+        let patch: Value = serde_yaml::from_str(
+            "db: {host: db.local, port: 5432}\n\
+             tags: [a, c]\n",
+        )
+        .unwrap();
+        assert_eq!(report.to_patch(), patch);
+
+        // The patch reproduces the load.
+        let b = Loader::new("patch")
+            .source(HashMap::new())
+            .load_patch(
+                &B::default(),
+                serde_yaml::to_string(&patch).unwrap().as_bytes(),
+            )
+            .unwrap();
+        assert_eq!(
+            (b.db.host, b.db.port, b.tags),
+            ("db.local".into(), 5432, vec!["a".into(), "c".into()])
+        );
+        assert_eq!(b.name, "");
+    }
+
+    #[test]
+    fn test_merge() {
+        #[derive(Serialize, Deserialize, Default)]
+        struct B {
+            motd: String,
+        }
+
+        let source = source(&[
+            ("MERGE_MOTD", "hello"),
+            ("MERGE_PORTS", "[1]"),
+            ("MERGE_UNKNOWN", "x"),
+        ]);
+        let loader = Loader::new("merge").source(source);
+
+        let (_, mut report) = loader.load_with_report(A::default()).unwrap();
+        let (_, other) = loader.load_with_report(B::default()).unwrap();
+        assert_eq!(report.applied.len(), 2);
+        assert_eq!(other.applied.len(), 1);
+
+        report.merge(other.clone());
+        let names: Vec<_> = report.applied.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["MERGE_MOTD", "MERGE_PORTS", "MERGE_MOTD"]);
+        assert_eq!(
+            report.checked,
+            vec!["MERGE_MOTD", "MERGE_PORTS", "MERGE_PASSWORD"]
+        );
+        assert_eq!(
+            report
+                .warnings
+                .iter()
+                .filter(|w| **w == Warning::UnusedVar("MERGE_UNKNOWN".into()))
+                .count(),
+            1
+        );
+
+        let mut all = crate::Report::default();
+        all.extend(vec![other.clone(), other]);
+        assert_eq!(all.applied.len(), 2);
+        assert_eq!(all.checked, vec!["MERGE_MOTD"]);
+    }
+}
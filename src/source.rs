@@ -0,0 +1,212 @@
+use crate::{Error, Result};
+use std::collections::HashMap;
+
+/// Source of environment variables.
+pub trait VarSource {
+    /// Get the value of the variable `name`, or `None` if it is not set.
+    fn var(&self, name: &str) -> Result<Option<String>>;
+
+    /// Get the names of all the variables in the source.
+    fn names(&self) -> Vec<String>;
+
+    /// Name of the source shown in warnings, e.g. `env` or `.env`.
+    fn label(&self) -> String {
+        "source".into()
+    }
+
+    /// Labels of the sources having the variable `name`, in the order of precedence.
+    fn origins(&self, name: &str) -> Result<Vec<String>> {
+        Ok(match self.var(name)? {
+            Some(_) => vec![self.label()],
+            None => Vec::new(),
+        })
+    }
+}
+
+/// The environment variables of the current process.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessEnv;
+
+impl VarSource for ProcessEnv {
+    fn var(&self, name: &str) -> Result<Option<String>> {
+        match std::env::var(name) {
+            Ok(val) => Ok(Some(val)),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(Error::VarError(e.to_string())),
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        std::env::vars_os()
+            .filter_map(|(name, _)| name.into_string().ok())
+            .collect()
+    }
+
+    fn label(&self) -> String {
+        "env".into()
+    }
+}
+
+impl VarSource for HashMap<String, String> {
+    fn var(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.get(name).cloned())
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.keys().cloned().collect()
+    }
+
+    fn label(&self) -> String {
+        "map".into()
+    }
+}
+
+/// Sources tried in order; a variable is taken from the first source having it.
+impl VarSource for Vec<Box<dyn VarSource + Send + Sync>> {
+    fn var(&self, name: &str) -> Result<Option<String>> {
+        for source in self {
+            if let Some(val) = source.var(name)? {
+                return Ok(Some(val));
+            }
+        }
+        Ok(None)
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.iter().flat_map(|s| s.names()).collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    fn origins(&self, name: &str) -> Result<Vec<String>> {
+        let mut origins = Vec::new();
+        for source in self {
+            origins.extend(source.origins(name)?);
+        }
+        Ok(origins)
+    }
+}
+
+/// Copy of the environment variables of the current process taken at a point in time.
+///
+/// Variables whose name or value is not valid unicode are not captured.
+#[derive(Clone, Debug, Default)]
+pub struct EnvSnapshot {
+    vars: HashMap<String, String>,
+}
+
+impl EnvSnapshot {
+    /// Capture the current environment variables.
+    pub fn capture() -> Self {
+        Self {
+            vars: std::env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+                .collect(),
+        }
+    }
+}
+
+impl VarSource for EnvSnapshot {
+    fn var(&self, name: &str) -> Result<Option<String>> {
+        self.vars.var(name)
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.vars.names()
+    }
+
+    fn label(&self) -> String {
+        "snapshot".into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::load_from;
+    use crate::test::{source, vars};
+    use serde::{Deserialize, Serialize};
+
+    #[test]
+    fn test_snapshot() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+            b: u32,
+        }
+
+        let _v = vars!(
+            "SNAPSHOT_A" => "1";
+        );
+        let snapshot = EnvSnapshot::capture();
+        let _v = vars!(
+            "SNAPSHOT_A" => "2";
+            "SNAPSHOT_B" => "2";
+        );
+        assert_eq!(
+            load_from("snapshot", &A::default(), snapshot).unwrap(),
+            A { a: 1, b: 0 }
+        );
+    }
+
+    #[test]
+    fn test_sources_precedence() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            a: u32,
+            b: u32,
+            c: u32,
+        }
+
+        let _v = vars!(
+            "CHAIN_A" => "1";
+        );
+        let dotenv = crate::DotEnv::parse("CHAIN_A=2\nCHAIN_B=2\n").unwrap();
+        let defaults = source(&[("CHAIN_A", "3"), ("CHAIN_B", "3"), ("CHAIN_C", "3")]);
+        let loader = crate::Loader::new("chain").sources(vec![
+            Box::new(ProcessEnv),
+            Box::new(dotenv),
+            Box::new(defaults),
+        ]);
+        assert_eq!(loader.load(&A::default()).unwrap(), A { a: 1, b: 2, c: 3 });
+    }
+
+    #[test]
+    fn test_sources_shadowed() {
+        #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq)]
+        struct A {
+            host: String,
+            port: u16,
+        }
+
+        let dotenv = crate::DotEnv::parse("SHADOW_HOST=dotenv.local\nSHADOW_PORT=80\n").unwrap();
+        let defaults = source(&[("SHADOW_HOST", "map.local")]);
+        let _v = vars!(
+            "SHADOW_HOST" => "env.local";
+        );
+        let (a, warnings) = crate::Loader::new("shadow")
+            .sources(vec![
+                Box::new(ProcessEnv),
+                Box::new(dotenv),
+                Box::new(defaults),
+            ])
+            .load_collect_warnings(&A::default())
+            .unwrap();
+        assert_eq!(
+            a,
+            A {
+                host: "env.local".into(),
+                port: 80,
+            }
+        );
+        assert_eq!(
+            warnings,
+            vec![crate::Warning::Shadowed(
+                "SHADOW_HOST".into(),
+                "env".into(),
+                vec![".env".into(), "map".into()]
+            )]
+        );
+    }
+}
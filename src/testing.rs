@@ -0,0 +1,116 @@
+//! Helpers for testing configuration loading, enabled by the `testing` feature.
+
+use std::cell::RefCell;
+use std::ffi::OsString;
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard};
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+thread_local! {
+    /// Number of the live guards of the thread, and the lock they hold.
+    static ENV_GUARD: RefCell<(usize, Option<MutexGuard<'static, ()>>)> = const { RefCell::new((0, None)) };
+}
+
+/// Guard of the process environment, shared by the tests of the crate and `ScopedVars`.
+///
+/// Guards nest on a thread, which holds the lock until its last guard is dropped.
+pub(crate) struct EnvLock(PhantomData<*const ()>);
+
+/// Lock the process environment.
+pub(crate) fn lock_env() -> EnvLock {
+    ENV_GUARD.with(|guard| {
+        let mut guard = guard.borrow_mut();
+        if guard.0 == 0 {
+            guard.1 = Some(ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner()));
+        }
+        guard.0 += 1;
+    });
+    EnvLock(PhantomData)
+}
+
+impl Drop for EnvLock {
+    fn drop(&mut self) {
+        ENV_GUARD.with(|guard| {
+            let mut guard = guard.borrow_mut();
+            guard.0 -= 1;
+            if guard.0 == 0 {
+                guard.1 = None;
+            }
+        });
+    }
+}
+
+/// Environment variables set for a scope.
+///
+/// The variables are set on construction and restored to their previous values,
+/// or removed if they were unset, on drop. The guard holds a process-wide lock
+/// while alive, so tests using `ScopedVars` don't race on the environment.
+/// Guards can be nested on a thread if dropped in reverse order.
+pub struct ScopedVars {
+    saved: Vec<(String, Option<OsString>)>,
+    _lock: EnvLock,
+}
+
+impl ScopedVars {
+    /// Set the variables `vars`.
+    pub fn new<I, K, V>(vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        let lock = lock_env();
+        let saved = vars
+            .into_iter()
+            .map(|(key, value)| {
+                let key = key.into();
+                let prev = std::env::var_os(&key);
+                std::env::set_var(&key, value.into());
+                (key, prev)
+            })
+            .collect();
+        Self { saved, _lock: lock }
+    }
+}
+
+impl Drop for ScopedVars {
+    fn drop(&mut self) {
+        for (key, prev) in self.saved.iter().rev() {
+            match prev {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scoped_vars_restore() {
+        std::env::set_var("SCOPED_KEPT", "original");
+
+        let vars = ScopedVars::new(vec![("SCOPED_KEPT", "scoped"), ("SCOPED_NEW", "new")]);
+        assert_eq!(std::env::var("SCOPED_KEPT").unwrap(), "scoped");
+        assert_eq!(std::env::var("SCOPED_NEW").unwrap(), "new");
+        drop(vars);
+
+        assert_eq!(std::env::var("SCOPED_KEPT").unwrap(), "original");
+        assert!(std::env::var_os("SCOPED_NEW").is_none());
+        std::env::remove_var("SCOPED_KEPT");
+    }
+
+    #[test]
+    fn test_scoped_vars_nested() {
+        let outer = ScopedVars::new(vec![("SCOPED_NESTED", "outer")]);
+        let inner = ScopedVars::new(vec![("SCOPED_NESTED", "inner")]);
+        assert_eq!(std::env::var("SCOPED_NESTED").unwrap(), "inner");
+        drop(inner);
+        assert_eq!(std::env::var("SCOPED_NESTED").unwrap(), "outer");
+        drop(outer);
+        assert!(std::env::var_os("SCOPED_NESTED").is_none());
+    }
+}
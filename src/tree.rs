@@ -0,0 +1,136 @@
+use crate::{Error, FieldKind, Loader, Report, Result, Serializer, VarSource};
+use serde::Serialize;
+use serde_yaml::Value;
+
+/// Canonical value tree of `t`, the base the environment variables are applied to.
+///
+/// Use it with `apply_overrides` to merge the variables into values from other sources.
+pub fn to_value_tree<T: Serialize>(t: &T) -> Result<Value> {
+    serde_yaml::to_value(t).map_err(Error::serialize)
+}
+
+/// Apply the environment variables starting with `pfx` in `source` to the value tree `value`.
+///
+/// Without type information, mappings are treated as structs and the kinds of
+/// other fields are taken from their current values.
+pub fn apply_overrides<S>(value: &mut Value, pfx: &str, source: S) -> Result<Report>
+where
+    S: VarSource + Send + Sync + 'static,
+{
+    Loader::new(pfx).source(source).apply_overrides(value)
+}
+
+impl Loader {
+    /// Apply the environment variables to the value tree `value`.
+    ///
+    /// Warnings are in the report instead of being passed to the sink.
+    pub fn apply_overrides(&self, value: &mut Value) -> Result<Report> {
+        let mut ser = Serializer::new(self, &self.prefix, value.clone());
+        ser.walk_value(value)?;
+        let (new, report) = ser.finish();
+        *value = new;
+        Ok(report)
+    }
+}
+
+impl Serializer<'_> {
+    /// Walk the value tree `value` as `serialize` walks typed values.
+    pub(crate) fn walk_value(&mut self, value: &Value) -> Result<()> {
+        match value {
+            Value::Mapping(map) => {
+                if self.curpath.len() > 1 || self.loader.root_var {
+                    self.load(FieldKind::Struct)?;
+                }
+                // Keys are sorted for the order of reports to be independent of insertion order.
+                let mut entries: Vec<_> = map
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.as_str()?, value)))
+                    .collect();
+                entries.sort_by_key(|(key, _)| *key);
+                for (key, value) in entries {
+                    self.child_value(key, value)?;
+                }
+                Ok(())
+            }
+            Value::Sequence(seq) => {
+                self.load(FieldKind::Seq)?;
+                for (i, value) in seq.iter().enumerate() {
+                    self.child_value(&i.to_string(), value)?;
+                }
+                self.append()?;
+                self.truncate()
+            }
+            Value::Bool(_) => self.load(FieldKind::Bool),
+            Value::Number(n) if n.is_f64() => self.load(FieldKind::Float),
+            Value::Number(_) => self.load(FieldKind::Int),
+            Value::String(_) => self.load(FieldKind::Str),
+            Value::Null => self.load(FieldKind::Option),
+        }
+    }
+
+    /// Same as `child` but for value trees.
+    pub(crate) fn child_value(&mut self, name: &str, value: &Value) -> Result<()> {
+        self.enter(name)?;
+        if self.loader.skips.contains(&self.field_path()) {
+            let path = self.path();
+            self.paths.insert(path);
+        } else if self.in_scope() || self.contains_scope() {
+            self.walk_value(value)?;
+        }
+        self.exit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::source;
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut value: Value = serde_yaml::from_str(
+            "name: app\n\
+             http: {port: 80, hosts: [a, b]}\n\
+             ratio: 0.5\n",
+        )
+        .unwrap();
+        let source = source(&[
+            ("TREE_HTTP_PORT", "8080"),
+            ("TREE_HTTP_HOSTS_1", "c"),
+            ("TREE_RATIO", "0.25"),
+            ("TREE_UNKNOWN", "1"),
+        ]);
+
+        let report = apply_overrides(&mut value, "tree", source).unwrap();
+        assert_eq!(
+            value,
+            serde_yaml::from_str::<Value>(
+                "name: app\n\
+                 http: {port: 8080, hosts: [a, c]}\n\
+                 ratio: 0.25\n",
+            )
+            .unwrap()
+        );
+        let names: Vec<_> = report.applied.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["TREE_HTTP_HOSTS_1", "TREE_HTTP_PORT", "TREE_RATIO"]
+        );
+        assert_eq!(
+            report.warnings,
+            vec![crate::Warning::UnusedVar("TREE_UNKNOWN".into())]
+        );
+    }
+
+    #[test]
+    fn test_to_value_tree() {
+        #[derive(Serialize)]
+        struct A {
+            port: u16,
+        }
+
+        let value = to_value_tree(&A { port: 80 }).unwrap();
+        assert_eq!(value, serde_yaml::from_str::<Value>("port: 80").unwrap());
+    }
+}
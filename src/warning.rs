@@ -0,0 +1,66 @@
+use std::fmt;
+
+/// Soft diagnostic found while loading environment variables.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// The environment variable maps to more than one field.
+    Ambiguous(String),
+    /// The environment variable has the prefix but maps to no field.
+    UnusedVar(String),
+    /// The environment variable is empty and treated as null.
+    EmptyCoerced(String),
+    /// The environment variable with an alias prefix is used in place of the second one.
+    DeprecatedPrefix(String, String),
+    /// The environment variables differing only in case all match the first one.
+    CaseCollision(String, Vec<String>),
+    /// Both the environment variable and its negation are set; the first one is used.
+    NegationConflict(String, String),
+    /// The environment variable is taken from the first source, shadowing it in the others.
+    Shadowed(String, String, Vec<String>),
+    /// The environment variable overrides a value set by the document of `load_patch`.
+    Conflict(String),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Ambiguous(path) => write!(f, "environment variable {} is ambiguous", path),
+            Warning::UnusedVar(name) => write!(f, "environment variable {} is not used", name),
+            Warning::EmptyCoerced(path) => {
+                write!(
+                    f,
+                    "environment variable {} is empty and treated as null",
+                    path
+                )
+            }
+            Warning::DeprecatedPrefix(alias, name) => write!(
+                f,
+                "environment variable {} is deprecated, use {} instead",
+                alias, name
+            ),
+            Warning::CaseCollision(path, names) => write!(
+                f,
+                "environment variables {} all match {}",
+                names.join(", "),
+                path
+            ),
+            Warning::NegationConflict(name, negated) => write!(
+                f,
+                "environment variables {} and {} are both set, {} is used",
+                name, negated, name
+            ),
+            Warning::Conflict(name) => write!(
+                f,
+                "environment variable {} overrides a value set by the document",
+                name
+            ),
+            Warning::Shadowed(name, source, shadowed) => write!(
+                f,
+                "environment variable {} is taken from {}, shadowing it in {}",
+                name,
+                source,
+                shadowed.join(", ")
+            ),
+        }
+    }
+}